@@ -22,9 +22,7 @@ fn main() {
 
     for path in &[
         "created_before",
-        "creation_time",
         "effective_time",
-        "expiration",
         "locked_time",
         "retention_expiration_time",
         "time_created",
@@ -38,6 +36,38 @@ fn main() {
         );
     }
 
+    // channel times are milliseconds since the epoch
+    for path in &[
+        "google.storage.v1.Channel.expiration",
+        "google.storage.v1.ListChannelsResponse.Items.creation_time",
+    ] {
+        config.field_attribute(
+            path,
+            r#"#[serde(with = "crate::serde::optional_timestamp_millis")]"#,
+        );
+    }
+
+    // the JSON API does not use camelCase for these fields
+    for (path, name) in &[
+        ("ServiceAccount.email_address", "email_address"),
+        ("ListChannelsResponse.Items.channel_id", "channel_id"),
+        (
+            "ListChannelsResponse.Items.creation_time",
+            "creation_time_ms",
+        ),
+        ("ListChannelsResponse.Items.push_url", "push_url"),
+        ("ListChannelsResponse.Items.resource_id", "resource_id"),
+        (
+            "ListChannelsResponse.Items.subscriber_email",
+            "subscriber_email",
+        ),
+    ] {
+        config.field_attribute(
+            format!("google.storage.v1.{}", path),
+            format!(r#"#[serde(rename = "{}")]"#, name),
+        );
+    }
+
     for path in &["fields", "update_mask"] {
         config.field_attribute(
             path,
@@ -1,9 +1,11 @@
+use crate::error::other_error;
+use crate::field_mask;
 use crate::google::storage::v1::common_enums::{
     PredefinedBucketAcl, PredefinedObjectAcl, Projection,
 };
 use crate::google::storage::v1::{
     DeleteBucketRequest, GetBucketRequest, InsertBucketRequest, ListBucketsRequest,
-    ListBucketsResponse, UpdateBucketRequest,
+    ListBucketsResponse, LockRetentionPolicyRequest, UpdateBucketRequest,
 };
 use crate::paginate::Paginate;
 use crate::query::Query;
@@ -118,6 +120,10 @@ impl Request for InsertBucketRequest {
 
     type Response = Bucket;
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         Ok(base_url.join("b")?)
     }
@@ -148,6 +154,10 @@ impl Request for DeleteBucketRequest {
 
     type Response = ();
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url.bucket(&self.bucket)
     }
@@ -251,6 +261,10 @@ impl Request for UpdateBucketRequest {
 
     type Response = Bucket;
 
+    fn is_idempotent(&self) -> bool {
+        self.if_metageneration_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url.bucket(&self.bucket)
     }
@@ -269,6 +283,76 @@ impl From<Bucket> for UpdateBucketRequest {
     }
 }
 
+impl Query for LockRetentionPolicyRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+
+        push_if!(self, query, if_metageneration_match);
+
+        query
+    }
+}
+
+impl Request for LockRetentionPolicyRequest {
+    const REQUEST_METHOD: Method = Method::POST;
+
+    type Response = Bucket;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url
+            .bucket(&self.bucket)?
+            .join_segment("lockRetentionPolicy")
+    }
+}
+
+impl From<Bucket> for LockRetentionPolicyRequest {
+    fn from(value: Bucket) -> Self {
+        LockRetentionPolicyRequest {
+            bucket: value.name,
+            if_metageneration_match: value.metageneration,
+            ..Default::default()
+        }
+    }
+}
+
+impl Query for PatchBucketRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+
+        push_if_opt!(self, query, if_metageneration_match);
+        push_if_opt!(self, query, if_metageneration_not_match);
+
+        push_enum!(self, query, PredefinedBucketAcl, predefined_acl);
+        push_enum!(
+            self,
+            query,
+            PredefinedObjectAcl,
+            predefined_default_object_acl
+        );
+        push_enum!(self, query, Projection, projection);
+
+        query
+    }
+}
+
+impl Request for PatchBucketRequest {
+    const REQUEST_METHOD: Method = Method::PATCH;
+
+    type Response = Bucket;
+
+    fn is_idempotent(&self) -> bool {
+        self.if_metageneration_match.is_some()
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url.bucket(&self.bucket)
+    }
+}
+
 impl Client {
     #[doc = " Creates a new bucket."]
     #[tracing::instrument]
@@ -329,11 +413,22 @@ impl Client {
 
     #[doc = " Updates a bucket. Changes to the bucket will be readable immediately after"]
     #[doc = " writing, but configuration changes may take time to propagate."]
+    #[doc = ""]
+    #[doc = " Only the fields named in `update_mask` are sent, masked fields left at"]
+    #[doc = " their default value are cleared. Without a mask every non-default field"]
+    #[doc = " of `metadata` is sent."]
+    #[tracing::instrument]
     pub async fn patch_bucket(
-        &mut self,
-        _request: impl Into<PatchBucketRequest>,
+        &self,
+        request: impl Into<PatchBucketRequest> + Debug,
     ) -> Result<Bucket> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let metadata = request.metadata.take().unwrap_or_default();
+        let body = field_mask::patch_body(&metadata, request.update_mask.as_ref())?;
+        drop(metadata);
+
+        self.invoke_json(request, body).await
     }
 
     #[doc = " Updates a bucket. Equivalent to PatchBucket, but always replaces all"]
@@ -353,6 +448,26 @@ impl Client {
         self.invoke_json(request, metadata).await
     }
 
+    #[doc = " Locks retention policy on a bucket."]
+    #[doc = ""]
+    #[doc = " `if_metageneration_match` is required, locking is irreversible and only"]
+    #[doc = " the retention policy that was last read should be locked."]
+    #[tracing::instrument]
+    pub async fn lock_bucket_retention_policy(
+        &self,
+        request: impl Into<LockRetentionPolicyRequest> + Debug,
+    ) -> Result<Bucket> {
+        let request = request.into();
+
+        if request.if_metageneration_match <= 0 {
+            return Err(other_error(
+                "Expected a positive if_metageneration_match field".into(),
+            ));
+        }
+
+        self.invoke(request).await
+    }
+
     #[doc = " Permanently deletes an empty bucket."]
     #[tracing::instrument]
     pub async fn delete_bucket(
@@ -1,3 +1,4 @@
+use crate::field_mask;
 use crate::google::storage::v1::{
     BucketAccessControl, DeleteBucketAccessControlRequest, GetBucketAccessControlRequest,
     InsertBucketAccessControlRequest, ListBucketAccessControlsRequest,
@@ -108,6 +109,26 @@ impl Request for ListBucketAccessControlsRequest {
     }
 }
 
+impl Query for PatchBucketAccessControlRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_request_params.take().request_query()
+    }
+}
+
+impl Request for PatchBucketAccessControlRequest {
+    const REQUEST_METHOD: Method = Method::PATCH;
+
+    type Response = BucketAccessControl;
+
+    fn scope(&self) -> &'static str {
+        crate::request::Scope::FULL_CONTROL
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        Ok(acl_url(base_url, &self.bucket)?.join(&self.entity)?)
+    }
+}
+
 impl Client {
     #[doc = " Creates a new ACL entry on the specified bucket."]
     #[tracing::instrument]
@@ -172,10 +193,19 @@ impl Client {
     }
 
     #[doc = " Updates an ACL entry on the specified bucket."]
+    #[doc = ""]
+    #[doc = " Only the fields named in `update_mask` are sent."]
+    #[tracing::instrument]
     pub async fn patch_bucket_access_control(
-        &mut self,
-        _request: impl Into<PatchBucketAccessControlRequest>,
+        &self,
+        request: impl Into<PatchBucketAccessControlRequest> + Debug,
     ) -> Result<BucketAccessControl> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let bucket_access_control = request.bucket_access_control.take().unwrap_or_default();
+        let body = field_mask::patch_body(&bucket_access_control, request.update_mask.as_ref())?;
+        drop(bucket_access_control);
+
+        self.invoke_json(request, body).await
     }
 }
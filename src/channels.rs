@@ -0,0 +1,190 @@
+use crate::error::other_error;
+use crate::google::storage::v1::common_enums::Projection;
+use crate::google::storage::v1::{
+    Channel, ListChannelsRequest, ListChannelsResponse, Object, StopChannelRequest,
+    WatchAllObjectsRequest,
+};
+use crate::query::Query;
+use crate::request::Request;
+use crate::urls::Urls;
+use crate::{push_enum, push_if, Client, Result};
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use std::fmt::Debug;
+use std::mem;
+use url::Url;
+
+impl Query for WatchAllObjectsRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+
+        push_if!(self, query, delimiter);
+        push_if!(self, query, include_trailing_delimiter);
+        push_if!(self, query, max_results);
+        push_if!(self, query, page_token);
+        push_if!(self, query, prefix);
+        push_enum!(self, query, Projection, projection);
+        push_if!(self, query, versions);
+
+        query
+    }
+}
+
+impl Request for WatchAllObjectsRequest {
+    const REQUEST_METHOD: Method = Method::POST;
+
+    type Response = Channel;
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url
+            .bucket(&self.bucket)?
+            .join_segment("o")?
+            .join_segment("watch")
+    }
+}
+
+impl Query for ListChannelsRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_request_params.request_query()
+    }
+}
+
+impl Request for ListChannelsRequest {
+    const REQUEST_METHOD: Method = Method::GET;
+
+    type Response = ListChannelsResponse;
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url.bucket(&self.bucket)?.join_segment("channels")
+    }
+}
+
+impl Query for StopChannelRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_request_params.request_query()
+    }
+}
+
+impl Request for StopChannelRequest {
+    const REQUEST_METHOD: Method = Method::POST;
+
+    type Response = ();
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url.join_segment("channels")?.join_segment("stop")
+    }
+}
+
+impl From<Channel> for StopChannelRequest {
+    fn from(value: Channel) -> Self {
+        StopChannelRequest {
+            channel: Some(value),
+            ..Default::default()
+        }
+    }
+}
+
+/// What happened to the resource watched by a channel
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelEvent {
+    /// Sent once when the channel is created
+    Sync,
+    /// An object was created, or its metadata was updated
+    Exists(Box<Object>),
+    /// An object was deleted, or archived by versioning
+    NotExists(Box<Object>),
+}
+
+/// An object change notification delivered to the address of a channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelNotification {
+    pub channel_id: String,
+    pub channel_token: Option<String>,
+    pub resource_id: String,
+    pub resource_uri: String,
+    pub message_number: i64,
+    pub event: ChannelEvent,
+}
+
+impl ChannelNotification {
+    /// Parse the headers and body of a webhook request sent to a channel address
+    pub fn from_request(headers: &HeaderMap, body: &[u8]) -> Result<Self> {
+        let header = |name: &str| -> Result<Option<String>> {
+            match headers.get(name) {
+                None => Ok(None),
+                Some(value) => Ok(Some(
+                    value
+                        .to_str()
+                        .map_err(|e| other_error(format!("Invalid {} header: {}", name, e)))?
+                        .to_string(),
+                )),
+            }
+        };
+
+        let required = |name: &str| -> Result<String> {
+            header(name)?.ok_or_else(|| other_error(format!("Expected {} header", name)))
+        };
+
+        let event = match required("x-goog-resource-state")?.as_str() {
+            "sync" => ChannelEvent::Sync,
+            "exists" => ChannelEvent::Exists(Box::new(serde_json::from_slice(body)?)),
+            "not_exists" => ChannelEvent::NotExists(Box::new(serde_json::from_slice(body)?)),
+            state => return Err(other_error(format!("Unexpected resource state {}", state))),
+        };
+
+        let message_number = required("x-goog-message-number")?;
+
+        Ok(ChannelNotification {
+            channel_id: required("x-goog-channel-id")?,
+            channel_token: header("x-goog-channel-token")?,
+            resource_id: required("x-goog-resource-id")?,
+            resource_uri: required("x-goog-resource-uri")?,
+            message_number: message_number
+                .parse()
+                .map_err(|_| other_error(format!("Invalid message number {}", message_number)))?,
+            event,
+        })
+    }
+}
+
+impl Client {
+    #[doc = " Watch for changes on all objects in a bucket."]
+    #[tracing::instrument]
+    pub async fn watch_all_objects(
+        &self,
+        request: impl Into<WatchAllObjectsRequest> + Debug,
+    ) -> Result<Channel> {
+        let mut request = request.into();
+
+        let channel = request.channel.take();
+
+        self.invoke_json(request, channel).await
+    }
+
+    #[doc = " List active object change notification channels for this bucket."]
+    #[tracing::instrument]
+    pub async fn list_channels(
+        &self,
+        request: impl Into<ListChannelsRequest> + Debug,
+    ) -> Result<ListChannelsResponse> {
+        let request = request.into();
+
+        self.invoke(request).await
+    }
+
+    #[doc = " Halts \"Object Change Notification\" push messagages."]
+    #[doc = " See https://cloud.google.com/storage/docs/object-change-notification"]
+    #[doc = " Note: this is not related to the newer \"Notifications\" resource, which"]
+    #[doc = " are stopped using DeleteNotification."]
+    #[tracing::instrument]
+    pub async fn stop_channel(&self, request: impl Into<StopChannelRequest> + Debug) -> Result<()> {
+        let mut request = request.into();
+
+        let channel = mem::take(&mut request.channel);
+
+        // the response has no body
+        self.request_json(request, channel).await?;
+
+        Ok(())
+    }
+}
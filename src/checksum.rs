@@ -0,0 +1,295 @@
+use crate::google::storage::v1::{DeleteObjectRequest, Object, ObjectChecksums};
+use crate::{Client, Result};
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING};
+use reqwest::{Response, StatusCode};
+use std::convert::TryInto;
+use tokio::sync::oneshot;
+
+const X_GOOG_HASH: &str = "x-goog-hash";
+
+const X_GOOG_STORED_CONTENT_ENCODING: &str = "x-goog-stored-content-encoding";
+
+/// The CRC32C and MD5 of an object's data, either as reported by the service or as computed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Hashes {
+    pub(crate) crc32c: Option<u32>,
+    pub(crate) md5: Option<Vec<u8>>,
+}
+
+impl Hashes {
+    /// Parse the `x-goog-hash` headers, such as `crc32c=n03x6A==,md5=Ojk9c3dhfxgoKVVHYwFbHQ==`,
+    /// which may also be sent as one header per hash
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut hashes = Hashes::default();
+
+        let values = headers
+            .get_all(X_GOOG_HASH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+
+        for value in values {
+            let mut parts = value.trim().splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+
+            let value = match base64::decode(value) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            match name {
+                "crc32c" => {
+                    hashes.crc32c = value.as_slice().try_into().ok().map(u32::from_be_bytes)
+                }
+                "md5" => hashes.md5 = Some(value),
+                _ => {}
+            }
+        }
+
+        hashes
+    }
+
+    /// The hashes the service reports for an object, composite objects have no MD5
+    pub(crate) fn of_object(object: &Object) -> Self {
+        Hashes {
+            crc32c: object.crc32c,
+            md5: base64::decode(&object.md5_hash)
+                .ok()
+                .filter(|md5| !md5.is_empty()),
+        }
+    }
+
+    /// The hashes to send with an upload
+    pub(crate) fn object_checksums(&self) -> ObjectChecksums {
+        ObjectChecksums {
+            crc32c: self.crc32c,
+            md5_hash: self.md5.as_ref().map(base64::encode).unwrap_or_default(),
+        }
+    }
+
+    /// The `x-goog-hash` header value
+    pub(crate) fn header_value(&self) -> String {
+        self.crc32c
+            .map(|crc32c| format!("crc32c={}", base64::encode(crc32c.to_be_bytes())))
+            .into_iter()
+            .chain(
+                self.md5
+                    .as_ref()
+                    .map(|md5| format!("md5={}", base64::encode(md5))),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Compare the expected hashes with the actual ones, skipping any that either side is missing
+    pub(crate) fn verify(&self, actual: &Hashes) -> Result<()> {
+        if let (Some(expected), Some(actual)) = (self.crc32c, actual.crc32c) {
+            if expected != actual {
+                return Err(crate::Error::ChecksumMismatch {
+                    algorithm: "crc32c",
+                    expected: base64::encode(expected.to_be_bytes()),
+                    actual: base64::encode(actual.to_be_bytes()),
+                    #[cfg(feature = "backtrace")]
+                    backtrace: std::backtrace::Backtrace::capture(),
+                });
+            }
+        }
+
+        if let (Some(expected), Some(actual)) = (&self.md5, &actual.md5) {
+            if expected != actual {
+                return Err(crate::Error::ChecksumMismatch {
+                    algorithm: "md5",
+                    expected: base64::encode(expected),
+                    actual: base64::encode(actual),
+                    #[cfg(feature = "backtrace")]
+                    backtrace: std::backtrace::Backtrace::capture(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the CRC32C and MD5 of data as it is streamed
+#[derive(Clone)]
+pub(crate) struct Hasher {
+    crc32c: u32,
+    md5: md5::Context,
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Hasher {
+            crc32c: 0,
+            md5: md5::Context::new(),
+        }
+    }
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.crc32c = crc32c::crc32c_append(self.crc32c, data);
+        self.md5.consume(data);
+    }
+
+    pub(crate) fn finish(self) -> Hashes {
+        Hashes {
+            crc32c: Some(self.crc32c),
+            md5: Some(self.md5.compute().0.to_vec()),
+        }
+    }
+}
+
+/// The MD5 of the data uploaded so far, which only lives as long as the process that computes it
+#[derive(Clone)]
+pub(crate) struct Md5(md5::Context);
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Md5(md5::Context::new())
+    }
+}
+
+impl Md5 {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.consume(data);
+    }
+
+    pub(crate) fn digest(&self) -> Vec<u8> {
+        self.0.clone().compute().0.to_vec()
+    }
+}
+
+impl std::fmt::Debug for Md5 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Md5")
+            .field(&base64::encode(self.digest()))
+            .finish()
+    }
+}
+
+impl PartialEq for Md5 {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest() == other.digest()
+    }
+}
+
+/// The hashes to validate a download against, or `None` when the data received is not the whole
+/// object as stored: a range read, or decompressive transcoding of a gzip object
+fn expected_hashes(response: &Response) -> Option<Hashes> {
+    if response.status() == StatusCode::PARTIAL_CONTENT {
+        return None;
+    }
+
+    let headers = response.headers();
+
+    let stored_encoding = headers
+        .get(X_GOOG_STORED_CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("identity");
+    let encoding = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("identity");
+    if !stored_encoding.eq_ignore_ascii_case(encoding) {
+        return None;
+    }
+
+    Some(Hashes::from_headers(headers)).filter(|hashes| *hashes != Hashes::default())
+}
+
+/// Check the whole body of a download against its `x-goog-hash`
+pub(crate) async fn validated_bytes(response: Response) -> Result<Bytes> {
+    let expected = expected_hashes(&response);
+    let bytes = response.bytes().await?;
+
+    if let Some(expected) = expected {
+        let mut hasher = Hasher::default();
+        hasher.update(&bytes);
+        expected.verify(&hasher.finish())?;
+    }
+
+    Ok(bytes)
+}
+
+/// Stream the body of a download, failing at the end of the stream if it does not match its
+/// `x-goog-hash`
+pub(crate) fn validated_stream(response: Response) -> impl Stream<Item = Result<Bytes>> + Unpin {
+    let expected = expected_hashes(&response);
+    let mut body = response.bytes_stream();
+
+    Box::pin(try_stream! {
+        let mut hasher = Hasher::default();
+
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            yield chunk;
+        }
+
+        if let Some(expected) = expected {
+            expected.verify(&hasher.finish())?;
+        }
+    })
+}
+
+/// Pass upload data through while hashing it, the hashes are sent once the stream ends
+pub(crate) fn hashed_stream<S>(
+    bytes: S,
+) -> (
+    impl Stream<Item = Result<Bytes>> + Send + Sync + 'static,
+    oneshot::Receiver<Hashes>,
+)
+where
+    S: Stream<Item = Bytes> + Send + Sync + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+
+    let stream = try_stream! {
+        let mut hasher = Hasher::default();
+
+        futures::pin_mut!(bytes);
+        while let Some(chunk) = bytes.next().await {
+            hasher.update(&chunk);
+            yield chunk;
+        }
+
+        // the receiver is only gone when the upload was abandoned
+        let _ = sender.send(hasher.finish());
+    };
+
+    (stream, receiver)
+}
+
+impl Client {
+    /// Check the hashes the service reports for an uploaded object against those computed while
+    /// sending it. On a mismatch the object is deleted when the client is configured to.
+    pub(crate) async fn verify_upload(&self, object: Object, computed: &Hashes) -> Result<Object> {
+        let err = match computed.verify(&Hashes::of_object(&object)) {
+            Ok(()) => return Ok(object),
+            Err(err) => err,
+        };
+
+        if self.delete_on_checksum_mismatch() {
+            let request = DeleteObjectRequest {
+                bucket: object.bucket.clone(),
+                object: object.name.clone(),
+                generation: object.generation,
+                ..Default::default()
+            };
+
+            if let Err(delete_err) = self.delete_object(request).await {
+                tracing::warn!(error = %delete_err, object = %object.name, "failed to delete corrupt object");
+            }
+        }
+
+        Err(err)
+    }
+}
@@ -1,5 +1,6 @@
 use crate::headers::Headers;
 use crate::request::Request;
+use crate::retry::RetryPolicy;
 use crate::{GoogleResponse, Result};
 use reqwest::{Body, RequestBuilder, Response};
 use serde::Serialize;
@@ -13,6 +14,10 @@ pub struct Client {
     client: reqwest::Client,
 
     base_url: Url,
+
+    retry_policy: RetryPolicy,
+
+    delete_on_checksum_mismatch: bool,
 }
 
 #[derive(Default)]
@@ -20,6 +25,8 @@ pub struct ClientBuilder {
     headers: Option<Box<dyn Headers>>,
     client: Option<reqwest::Client>,
     base_url: Option<Url>,
+    retry_policy: Option<RetryPolicy>,
+    delete_on_checksum_mismatch: bool,
 }
 
 impl ClientBuilder {
@@ -29,6 +36,24 @@ impl ClientBuilder {
         self.headers(token)
     }
 
+    #[cfg(feature = "yup-oauth2")]
+    pub fn authenticator<C>(
+        self,
+        authenticator: yup_oauth2::authenticator::Authenticator<C>,
+    ) -> Self
+    where
+        C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+    {
+        let authenticator: Box<dyn Headers> = Box::new(authenticator);
+        self.headers(authenticator)
+    }
+
+    /// Send requests without credentials, for public buckets and objects
+    pub fn anonymous(self) -> Self {
+        let headers: Box<dyn Headers> = Box::new(());
+        self.headers(headers)
+    }
+
     pub fn headers(mut self, headers: impl Into<Box<dyn Headers>>) -> Self {
         self.headers = Some(headers.into());
         self
@@ -44,6 +69,19 @@ impl ClientBuilder {
         self
     }
 
+    /// How requests that fail with a transient error are retried, defaults to `RetryPolicy::default()`
+    pub fn retry_policy(mut self, retry_policy: impl Into<RetryPolicy>) -> Self {
+        self.retry_policy = Some(retry_policy.into());
+        self
+    }
+
+    /// Delete an uploaded object when its checksums do not match the data that was sent,
+    /// defaults to `false` which leaves the object in place
+    pub fn delete_on_checksum_mismatch(mut self, delete_on_checksum_mismatch: bool) -> Self {
+        self.delete_on_checksum_mismatch = delete_on_checksum_mismatch;
+        self
+    }
+
     pub fn build(self) -> Result<Client> {
         let client = self.client.unwrap_or_default();
 
@@ -57,6 +95,8 @@ impl ClientBuilder {
             headers,
             client,
             base_url,
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            delete_on_checksum_mismatch: self.delete_on_checksum_mismatch,
         })
     }
 }
@@ -67,6 +107,11 @@ impl Debug for Client {
             .field("token", &"...")
             .field("client", &self.client)
             .field("base_url", &self.base_url.to_string())
+            .field("retry_policy", &self.retry_policy)
+            .field(
+                "delete_on_checksum_mismatch",
+                &self.delete_on_checksum_mismatch,
+            )
             .finish()
     }
 }
@@ -76,15 +121,26 @@ impl Client {
         Default::default()
     }
 
-    /// Create a new storage client with the default authentication scope
+    /// Create a new storage client using the application default credentials,
+    /// see `application_default_credentials`
     #[tracing::instrument]
     pub fn new() -> Result<Self> {
-        Self::builder().build()
+        Self::builder()
+            .headers(crate::credentials::application_default_credentials()?)
+            .build()
     }
 }
 
 impl Client {
-    fn request_builder<R: Request>(&self, mut request: R) -> Result<RequestBuilder> {
+    pub(crate) fn delete_on_checksum_mismatch(&self) -> bool {
+        self.delete_on_checksum_mismatch
+    }
+
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    async fn request_builder<R: Request>(&self, mut request: R) -> Result<RequestBuilder> {
         let path = request.request_path(self.base_url.clone())?;
 
         tracing::debug!(request_path = %path);
@@ -92,7 +148,7 @@ impl Client {
         Ok(self
             .client
             .request(R::REQUEST_METHOD, path)
-            .headers(self.headers.headers(request.scope())?)
+            .headers(self.headers.headers(request.scope()).await?)
             .headers(request.request_headers())
             .query(&request.request_query()))
     }
@@ -101,23 +157,28 @@ impl Client {
         self.request_body(request, vec![]).await
     }
 
-    async fn request_body<R: Request>(
+    pub(crate) async fn request_body<R: Request>(
         &self,
         request: R,
         body: impl Into<Body>,
     ) -> Result<Response> {
-        Ok(self
-            .request_builder(request)?
-            .body(body)
-            .send()
-            .instrument(tracing::trace_span!("sending"))
-            .await?
-            .into_google_response()
-            .instrument(tracing::trace_span!("error test"))
-            .await?)
+        let idempotent = request.is_idempotent();
+
+        self.retry_policy
+            .send(self.request_builder(request).await?.body(body), idempotent)
+            .await
+    }
+
+    /// Send a request once, for callers that retry it themselves
+    pub(crate) async fn request_body_once<R: Request>(
+        &self,
+        request: R,
+        body: impl Into<Body>,
+    ) -> Result<Response> {
+        crate::retry::send(self.request_builder(request).await?.body(body)).await
     }
 
-    async fn request_json<R: Request, T: Serialize>(
+    pub(crate) async fn request_json<R: Request, T: Serialize>(
         &self,
         request: R,
         body: T,
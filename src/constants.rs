@@ -16,10 +16,12 @@ pub(crate) const include_trailing_delimiter: &str = "includeTrailingDelimiter";
 pub(crate) const kms_key_name: &str = "kmsKeyName";
 pub(crate) const max_bytes_rewritten_per_call: &str = "maxBytesRewrittenPerCall";
 pub(crate) const max_results: &str = "maxResults";
-pub(crate) const name: &str = "name";
 pub(crate) const page_token: &str = "pageToken";
+pub(crate) const predefined_acl: &str = "predefinedAcl";
+pub(crate) const predefined_default_object_acl: &str = "predefinedDefaultObjectAcl";
 pub(crate) const prefix: &str = "prefix";
 pub(crate) const project: &str = "project";
+pub(crate) const projection: &str = "projection";
 pub(crate) const quota_user: &str = "quotaUser";
 pub(crate) const rewrite_token: &str = "rewriteToken";
 pub(crate) const service_account_email: &str = "serviceAccountEmail";
@@ -46,6 +48,12 @@ macro_rules! push_if_opt {
 #[macro_export]
 macro_rules! push_enum {
     ($self:ident, $query:ident, $enum:ident, $x:ident) => {{
-        $query.extend($enum::from_i32(mem::take(&mut $self.$x)).request_query())
+        use $crate::query::QueryValue;
+        if let Some(value) = $enum::from_i32(mem::take(&mut $self.$x))
+            .as_ref()
+            .and_then(QueryValue::query_value)
+        {
+            $query.push(($crate::constants::$x, value.to_string()));
+        }
     }};
 }
@@ -1,3 +1,4 @@
+use crate::field_mask;
 use crate::google::storage::v1::{
     DeleteDefaultObjectAccessControlRequest, GetDefaultObjectAccessControlRequest,
     InsertDefaultObjectAccessControlRequest, ListDefaultObjectAccessControlsRequest,
@@ -13,7 +14,7 @@ use std::fmt::Debug;
 use url::Url;
 
 fn default_object_acl_url(base_url: Url, bucket: &str) -> Result<Url> {
-    base_url.bucket(bucket)?.join_segment("defaultObjectAcl")
+    base_url.bucket(bucket)?.join_segment("defaultObjectAcl/")
 }
 
 impl Query for InsertDefaultObjectAccessControlRequest {
@@ -113,6 +114,26 @@ impl Request for DeleteDefaultObjectAccessControlRequest {
     }
 }
 
+impl Query for PatchDefaultObjectAccessControlRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_request_params.take().request_query()
+    }
+}
+
+impl Request for PatchDefaultObjectAccessControlRequest {
+    const REQUEST_METHOD: Method = Method::PATCH;
+
+    type Response = ObjectAccessControl;
+
+    fn scope(&self) -> &'static str {
+        crate::request::Scope::FULL_CONTROL
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        Ok(default_object_acl_url(base_url, &self.bucket)?.join(&self.entity)?)
+    }
+}
+
 impl Client {
     #[doc = " Creates a new default object ACL entry on the specified bucket."]
     #[tracing::instrument]
@@ -151,11 +172,20 @@ impl Client {
     }
 
     #[doc = " Updates a default object ACL entry on the specified bucket."]
+    #[doc = ""]
+    #[doc = " Only the fields named in `update_mask` are sent."]
+    #[tracing::instrument]
     pub async fn patch_default_object_access_control(
-        &mut self,
-        _request: impl Into<PatchDefaultObjectAccessControlRequest>,
+        &self,
+        request: impl Into<PatchDefaultObjectAccessControlRequest> + Debug,
     ) -> Result<ObjectAccessControl> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let object_access_control = request.object_access_control.take().unwrap_or_default();
+        let body = field_mask::patch_body(&object_access_control, request.update_mask.as_ref())?;
+        drop(object_access_control);
+
+        self.invoke_json(request, body).await
     }
 
     #[doc = " Updates a default object ACL entry on the specified bucket. Equivalent to"]
@@ -1,11 +1,13 @@
-use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
 
 // from https://cloud.google.com/storage/docs/request-endpoints
 // !, #, $, &, ', (, ), *, +, ,, /, :, ;, =, ?, @, [, ]
+// and % so names are not read as already encoded
 const NORMAL_SET: &AsciiSet = &CONTROLS // no way to start with an empty set...
     .add(b'!')
     .add(b'#')
     .add(b'$')
+    .add(b'%')
     .add(b'&')
     .add(b'\'')
     .add(b'(')
@@ -30,3 +32,29 @@ const SLASH_SET: &AsciiSet = &NORMAL_SET.add(b'/');
 pub(crate) fn slash(input: impl AsRef<str>) -> String {
     utf8_percent_encode(input.as_ref(), SLASH_SET).to_string()
 }
+
+// the canonical request of a V4 signature leaves only the unreserved characters from
+// RFC 3986 unencoded, see https://cloud.google.com/storage/docs/authentication/canonical-requests
+//
+// `normal` and `slash` cannot be reused: they leave characters such as space, `"`, `^` and `|`
+// unencoded, and `Url` escapes some of those when a request is built and sends the rest as they
+// are. The canonical request is hashed as a string before any URL exists, so every character
+// outside the unreserved set must already be encoded the way the service encodes it. Query
+// parameters differ too, `Url` encodes a space in a query as `+` where V4 expects `%20`.
+const SIGNING_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Encode a bucket name or query parameter for a V4 signature
+pub(crate) fn signing(input: impl AsRef<str>) -> String {
+    utf8_percent_encode(input.as_ref(), SIGNING_SET).to_string()
+}
+
+const SIGNING_PATH_SET: &AsciiSet = &SIGNING_SET.remove(b'/');
+
+/// Encode an object name for a V4 signature, the `/` between its segments is kept
+pub(crate) fn signing_path(input: impl AsRef<str>) -> String {
+    utf8_percent_encode(input.as_ref(), SIGNING_PATH_SET).to_string()
+}
@@ -19,6 +19,14 @@ pub enum Error {
         #[cfg(feature = "backtrace")]
         backtrace: Backtrace,
     },
+    #[cfg(feature = "yup-oauth2")]
+    #[error(transparent)]
+    YupOAuth2 {
+        #[from]
+        source: yup_oauth2::Error,
+        #[cfg(feature = "backtrace")]
+        backtrace: Backtrace,
+    },
     #[error(transparent)]
     IOError {
         #[from]
@@ -54,6 +62,21 @@ pub enum Error {
         #[cfg(feature = "backtrace")]
         backtrace: Backtrace,
     },
+    #[error("Failed to refresh the access token for {scope}")]
+    TokenRefresh {
+        scope: String,
+        source: Box<Error>,
+        #[cfg(feature = "backtrace")]
+        backtrace: Backtrace,
+    },
+    #[error("The {algorithm} checksum {actual} does not match the expected {expected}")]
+    ChecksumMismatch {
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+        #[cfg(feature = "backtrace")]
+        backtrace: Backtrace,
+    },
     #[error("Invalid request url {url}")]
     InvalidRequestUrl {
         url: Url,
@@ -68,3 +91,12 @@ pub enum Error {
         backtrace: Backtrace,
     },
 }
+
+/// An `Error::Other` with a message describing what went wrong
+pub(crate) fn other_error(message: String) -> Error {
+    Error::Other {
+        source: message.into(),
+        #[cfg(feature = "backtrace")]
+        backtrace: Backtrace::capture(),
+    }
+}
@@ -0,0 +1,75 @@
+use crate::Result;
+use prost_types::FieldMask;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Map valued fields, the path segment that follows one of these is a key and not a field name
+const MAP_FIELDS: &[&str] = &["labels", "metadata"];
+
+fn camel_case(field: &str) -> String {
+    let mut output = String::with_capacity(field.len());
+    let mut upper = false;
+
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            output.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            output.push(c);
+        }
+    }
+
+    output
+}
+
+/// Build a JSON API `PATCH` body containing only the fields of `value` named in `mask`.
+///
+/// Masked fields that have their default value are sent as `null` so the service clears them.
+/// Without a mask, or with the `*` mask, every non-default field is sent.
+pub(crate) fn patch_body<T: Serialize>(value: &T, mask: Option<&FieldMask>) -> Result<Value> {
+    let value = serde_json::to_value(value)?;
+
+    let paths = match mask {
+        Some(mask) if !mask.paths.is_empty() && !mask.paths.iter().any(|path| path == "*") => {
+            &mask.paths
+        }
+        _ => return Ok(value),
+    };
+
+    let mut body = Map::new();
+
+    'paths: for path in paths {
+        let mut source = Some(&value);
+        let mut target = &mut body;
+        let mut is_key = false;
+        let mut segments = path.split('.').peekable();
+
+        while let Some(segment) = segments.next() {
+            let key = if is_key {
+                segment.to_string()
+            } else {
+                camel_case(segment)
+            };
+
+            is_key = !is_key && MAP_FIELDS.contains(&key.as_str());
+            source = source.and_then(|source| source.get(&key));
+
+            if segments.peek().is_none() {
+                target.insert(key, source.cloned().unwrap_or(Value::Null));
+            } else {
+                target = match target
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    Value::Object(map) => map,
+                    // a parent path is already being cleared
+                    _ => continue 'paths,
+                };
+            }
+        }
+    }
+
+    Ok(Value::Object(body))
+}
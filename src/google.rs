@@ -30,6 +30,18 @@ pub mod error {
         error: Errors,
     }
 
+    impl ErrorResponse {
+        /// The HTTP status code of the error
+        pub fn code(&self) -> u16 {
+            self.error.code
+        }
+
+        /// The error message
+        pub fn message(&self) -> &str {
+            &self.error.message
+        }
+    }
+
     #[derive(Debug, Default, serde::Deserialize)]
     #[serde(default, rename = "camelCase")]
     pub struct Errors {
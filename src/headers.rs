@@ -1,19 +1,31 @@
 use crate::Result;
+use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue};
 
-pub trait Headers {
-    fn headers(&self, scope: &str) -> Result<HeaderMap<HeaderValue>>;
+/// Supplies the headers, typically `Authorization`, added to every request
+#[async_trait]
+pub trait Headers: Send + Sync {
+    async fn headers(&self, scope: &str) -> Result<HeaderMap<HeaderValue>>;
 }
 
+#[async_trait]
+impl<H: Headers + ?Sized> Headers for Box<H> {
+    async fn headers(&self, scope: &str) -> Result<HeaderMap<HeaderValue>> {
+        (**self).headers(scope).await
+    }
+}
+
+#[async_trait]
 impl Headers for () {
-    fn headers(&self, _scope: &str) -> Result<HeaderMap<HeaderValue>> {
+    async fn headers(&self, _scope: &str) -> Result<HeaderMap<HeaderValue>> {
         Ok(HeaderMap::new())
     }
 }
 
 #[cfg(feature = "gouth")]
+#[async_trait]
 impl Headers for gouth::Token {
-    fn headers(&self, _scopes: &str) -> Result<HeaderMap<HeaderValue>> {
+    async fn headers(&self, _scopes: &str) -> Result<HeaderMap<HeaderValue>> {
         let mut map = HeaderMap::with_capacity(1);
         map.insert(
             reqwest::header::AUTHORIZATION,
@@ -23,9 +35,19 @@ impl Headers for gouth::Token {
     }
 }
 
+/// An authenticator built with the default hyper client, as returned by the yup-oauth2 builders
+#[cfg(feature = "yup-oauth2")]
+pub type Authenticator = yup_oauth2::authenticator::Authenticator<
+    <yup_oauth2::authenticator::DefaultHyperClient as yup_oauth2::authenticator::HyperClientBuilder>::Connector,
+>;
+
 #[cfg(feature = "yup-oauth2")]
-impl Headers for yup_oauth2::authenticator::Authenticator<C> {
-    fn headers(&self, scope: &str) -> Result<HeaderMap<HeaderValue>> {
+#[async_trait]
+impl<C> Headers for yup_oauth2::authenticator::Authenticator<C>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    async fn headers(&self, scope: &str) -> Result<HeaderMap<HeaderValue>> {
         let token = self.token(&[scope]).await?;
         let mut map = HeaderMap::with_capacity(1);
         map.insert(
@@ -117,6 +117,13 @@ impl Request for UpdateHmacKeyRequest {
         crate::request::Scope::FULL_CONTROL
     }
 
+    fn is_idempotent(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .map(|metadata| !metadata.etag.is_empty())
+            .unwrap_or_default()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         Ok(hmac_keys_url(base_url, &self.project_id)?.join(&self.access_id)?)
     }
@@ -137,6 +144,10 @@ impl Request for DeleteHmacKeyRequest {
         crate::request::Scope::FULL_CONTROL
     }
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         Ok(hmac_keys_url(base_url, &self.project_id)?.join(&self.access_id)?)
     }
@@ -1,28 +1,96 @@
+use crate::error::other_error;
 use crate::google::iam::v1::{Policy, TestIamPermissionsResponse};
 use crate::google::storage::v1::{
-    GetIamPolicyRequest, SetIamPolicyRequest, TestIamPermissionsRequest,
+    GetIamPolicyRequest, Object, SetIamPolicyRequest, TestIamPermissionsRequest,
 };
 use crate::query::{PushIf, Query};
 use crate::request::Request;
 use crate::urls::Urls;
-use crate::{Client, Result};
+use crate::{constants, Client, Result};
 use reqwest::Method;
 use std::fmt::Debug;
 use url::Url;
 
-fn iam_url<'a, R, F>(base_url: Url, iam_request: Option<&'a R>, resource: F) -> Result<Url>
-where
-    F: FnOnce(&'a R) -> &'a str,
-{
-    let request = iam_request.ok_or(crate::Error::Other {
-        source: "Expected iam_request field".into(),
-        #[cfg(feature = "backtrace")]
-        backtrace: std::backtrace::Backtrace::capture(),
-    })?;
+pub use crate::google::iam::v1;
 
-    let resource = resource(request);
+/// Names the object an IAM request applies to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectResource {
+    pub bucket: String,
+    pub object: String,
+    /// Selects a version of the object, the live version when zero
+    pub generation: i64,
+}
+
+impl From<Object> for ObjectResource {
+    fn from(value: Object) -> Self {
+        ObjectResource {
+            bucket: value.bucket,
+            object: value.name,
+            generation: value.generation,
+        }
+    }
+}
+
+/// The `iam` url of a bucket, or of an object in it
+fn iam_url(base_url: Url, bucket: &str, object: Option<&str>) -> Result<Url> {
+    let url = base_url.bucket(bucket)?;
 
-    base_url.bucket(resource)?.join_segment("iam")
+    match object {
+        Some(object) => url.slash_object(object)?,
+        None => url,
+    }
+    .join_segment("iam")
+}
+
+/// The name of the bucket an IAM request applies to
+fn bucket_resource(resource: Option<&str>) -> Result<&str> {
+    resource.ok_or_else(|| other_error("Expected iam_request field".into()))
+}
+
+/// The bucket and object IAM requests share a message, only their url differs
+trait IamRequest: Request {
+    /// The path below the `iam` url of the resource
+    fn iam_path(url: Url) -> Result<Url> {
+        Ok(url)
+    }
+}
+
+/// An IAM request that applies to an object instead of the bucket named by its resource
+#[derive(Debug)]
+struct ObjectIamRequest<R> {
+    object: ObjectResource,
+    request: R,
+}
+
+impl<R: IamRequest> Query for ObjectIamRequest<R> {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.request.request_query();
+        query.push_if(constants::generation, &mut self.object.generation);
+        query
+    }
+}
+
+impl<R: IamRequest> Request for ObjectIamRequest<R> {
+    const REQUEST_METHOD: Method = R::REQUEST_METHOD;
+
+    type Response = R::Response;
+
+    fn scope(&self) -> &'static str {
+        self.request.scope()
+    }
+
+    fn is_idempotent(&self) -> bool {
+        self.request.is_idempotent()
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        R::iam_path(iam_url(
+            base_url,
+            &self.object.bucket,
+            Some(&self.object.object),
+        )?)
+    }
 }
 
 impl Query for GetIamPolicyRequest {
@@ -50,10 +118,13 @@ impl Request for GetIamPolicyRequest {
     type Response = Policy;
 
     fn request_path(&self, base_url: Url) -> Result<Url> {
-        iam_url(base_url, self.iam_request.as_ref(), |r| &r.resource)
+        let bucket = bucket_resource(self.iam_request.as_ref().map(|r| r.resource.as_str()))?;
+        Self::iam_path(iam_url(base_url, bucket, None)?)
     }
 }
 
+impl IamRequest for GetIamPolicyRequest {}
+
 impl Query for SetIamPolicyRequest {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
         self.common_request_params.take().request_query()
@@ -69,11 +140,22 @@ impl Request for SetIamPolicyRequest {
         crate::request::Scope::FULL_CONTROL
     }
 
+    fn is_idempotent(&self) -> bool {
+        self.iam_request
+            .as_ref()
+            .and_then(|request| request.policy.as_ref())
+            .map(|policy| !policy.etag.is_empty())
+            .unwrap_or_default()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
-        iam_url(base_url, self.iam_request.as_ref(), |r| &r.resource)
+        let bucket = bucket_resource(self.iam_request.as_ref().map(|r| r.resource.as_str()))?;
+        Self::iam_path(iam_url(base_url, bucket, None)?)
     }
 }
 
+impl IamRequest for SetIamPolicyRequest {}
+
 impl Query for TestIamPermissionsRequest {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
         let mut query = self.common_request_params.take().request_query();
@@ -97,10 +179,14 @@ impl Request for TestIamPermissionsRequest {
     type Response = TestIamPermissionsResponse;
 
     fn request_path(&self, base_url: Url) -> Result<Url> {
-        Ok(
-            iam_url(base_url, self.iam_request.as_ref(), |r| &r.resource)?
-                .join("testPermissions")?,
-        )
+        let bucket = bucket_resource(self.iam_request.as_ref().map(|r| r.resource.as_str()))?;
+        Self::iam_path(iam_url(base_url, bucket, None)?)
+    }
+}
+
+impl IamRequest for TestIamPermissionsRequest {
+    fn iam_path(url: Url) -> Result<Url> {
+        url.join_segment("testPermissions")
     }
 }
 
@@ -111,9 +197,7 @@ impl Client {
         &self,
         request: impl Into<GetIamPolicyRequest> + Debug,
     ) -> crate::Result<Policy> {
-        let request = request.into();
-
-        self.invoke(request).await
+        self.get_iam_policy(None, request.into()).await
     }
 
     #[doc = " Updates an IAM policy for the specified bucket."]
@@ -122,11 +206,7 @@ impl Client {
         &self,
         request: impl Into<SetIamPolicyRequest> + Debug,
     ) -> crate::Result<Policy> {
-        let mut request = request.into();
-
-        let policy = request.iam_request.take().and_then(|r| r.policy);
-
-        self.invoke_json(request, policy).await
+        self.set_iam_policy(None, request.into()).await
     }
 
     #[doc = " Tests a set of permissions on the given bucket to see which, if"]
@@ -136,8 +216,85 @@ impl Client {
         &self,
         request: impl Into<TestIamPermissionsRequest> + Debug,
     ) -> crate::Result<TestIamPermissionsResponse> {
-        let request = request.into();
+        self.test_iam_permissions(None, request.into()).await
+    }
+}
+
+impl Client {
+    #[doc = " Gets the IAM policy for the specified object. The object is named by"]
+    #[doc = " `object`, the resource of the request is not used."]
+    #[tracing::instrument]
+    pub async fn get_object_iam_policy(
+        &self,
+        object: impl Into<ObjectResource> + Debug,
+        request: impl Into<GetIamPolicyRequest> + Debug,
+    ) -> crate::Result<Policy> {
+        self.get_iam_policy(Some(object.into()), request.into())
+            .await
+    }
+
+    #[doc = " Updates an IAM policy for the specified object. The object is named by"]
+    #[doc = " `object`, the resource of the request is not used."]
+    #[tracing::instrument]
+    pub async fn set_object_iam_policy(
+        &self,
+        object: impl Into<ObjectResource> + Debug,
+        request: impl Into<SetIamPolicyRequest> + Debug,
+    ) -> crate::Result<Policy> {
+        self.set_iam_policy(Some(object.into()), request.into())
+            .await
+    }
+
+    #[doc = " Tests a set of permissions on the given object to see which, if"]
+    #[doc = " any, are held by the caller. The object is named by `object`, the"]
+    #[doc = " resource of the request is not used."]
+    #[tracing::instrument]
+    pub async fn test_object_iam_permissions(
+        &self,
+        object: impl Into<ObjectResource> + Debug,
+        request: impl Into<TestIamPermissionsRequest> + Debug,
+    ) -> crate::Result<TestIamPermissionsResponse> {
+        self.test_iam_permissions(Some(object.into()), request.into())
+            .await
+    }
+}
+
+impl Client {
+    async fn get_iam_policy(
+        &self,
+        object: Option<ObjectResource>,
+        request: GetIamPolicyRequest,
+    ) -> crate::Result<Policy> {
+        match object {
+            Some(object) => self.invoke(ObjectIamRequest { object, request }).await,
+            None => self.invoke(request).await,
+        }
+    }
+
+    async fn set_iam_policy(
+        &self,
+        object: Option<ObjectResource>,
+        request: SetIamPolicyRequest,
+    ) -> crate::Result<Policy> {
+        let policy = request.iam_request.as_ref().and_then(|r| r.policy.clone());
 
-        self.invoke(request).await
+        match object {
+            Some(object) => {
+                self.invoke_json(ObjectIamRequest { object, request }, policy)
+                    .await
+            }
+            None => self.invoke_json(request, policy).await,
+        }
+    }
+
+    async fn test_iam_permissions(
+        &self,
+        object: Option<ObjectResource>,
+        request: TestIamPermissionsRequest,
+    ) -> crate::Result<TestIamPermissionsResponse> {
+        match object {
+            Some(object) => self.invoke(ObjectIamRequest { object, request }).await,
+            None => self.invoke(request).await,
+        }
     }
 }
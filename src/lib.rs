@@ -2,29 +2,52 @@
 
 mod bucket;
 mod bucket_access_control;
+mod channels;
+mod checksum;
 mod client;
 mod constants;
+mod credentials;
 mod default_object_access_control;
 mod encode;
 mod error;
+mod field_mask;
 mod google;
 mod headers;
 mod hmac_key;
-mod iam;
+pub mod iam;
 mod notifications;
 mod object;
 mod object_access_control;
 mod paginate;
+mod post_policy;
 mod query;
 mod request;
+mod resumable;
+mod retry;
 mod serde;
+mod service_account;
+mod signed_url;
+mod signer;
 mod urls;
 
 #[cfg(test)]
 mod tests;
 
 pub use crate::error::*;
+pub use channels::{ChannelEvent, ChannelNotification};
 pub use client::{Client, ClientBuilder};
+pub use credentials::{
+    application_default_credentials, AccessToken, AuthorizedUserCredentials, CachedCredentials,
+    ImpersonatedCredentials, MetadataServerCredentials, ServiceAccountCredentials, TokenSource,
+};
 pub use google::*;
+#[cfg(feature = "yup-oauth2")]
+pub use headers::Authenticator;
+pub use headers::Headers;
+pub use post_policy::{PostPolicy, PostPolicyV4};
+pub use resumable::ResumableUpload;
+pub use retry::RetryPolicy;
+pub use signed_url::{SignedUrl, UrlStyle};
+pub use signer::{IamSigner, Signer, UrlSigner};
 
 pub type Result<T> = std::result::Result<T, crate::Error>;
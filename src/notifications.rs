@@ -26,6 +26,10 @@ impl Request for DeleteNotificationRequest {
 
     type Response = ();
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         Ok(notification_configs_url(base_url, &self.bucket)?.join(&self.notification)?)
     }
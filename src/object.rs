@@ -1,26 +1,28 @@
+use crate::checksum;
+use crate::error::other_error;
+use crate::field_mask;
 use crate::google::storage::v1::common_enums::{PredefinedObjectAcl, Projection};
 use crate::google::storage::v1::compose_object_request::SourceObjects;
-use crate::google::storage::v1::insert_object_request::FirstMessage;
+use crate::google::storage::v1::insert_object_request::{Data, FirstMessage};
 use crate::google::storage::v1::{
     Bucket, CommonObjectRequestParams, CommonRequestParams, ComposeObjectRequest,
     CopyObjectRequest, DeleteObjectRequest, GetObjectMediaRequest, GetObjectRequest,
     InsertObjectRequest, ListObjectsRequest, ListObjectsResponse, ObjectChecksums,
-    RewriteObjectRequest, RewriteResponse, StartResumableWriteRequest, UpdateObjectRequest,
+    RewriteObjectRequest, RewriteResponse, UpdateObjectRequest,
 };
 use crate::paginate::Paginate;
 use crate::query::{PushIf, Query};
 use crate::request::Request;
-use crate::storage::v1::{
-    InsertObjectSpec, Object, PatchObjectRequest, QueryWriteStatusRequest,
-    QueryWriteStatusResponse, StartResumableWriteResponse,
-};
+use crate::resumable::WriteStatus;
+use crate::storage::v1::{InsertObjectSpec, Object, PatchObjectRequest};
 use crate::urls::Urls;
 use crate::Result;
-use crate::{constants, push_enum, push_if, push_if_opt, Client};
+use crate::{push_enum, push_if, push_if_opt, Client};
 use bytes::Bytes;
-use futures::{Stream, StreamExt, TryStreamExt};
-use reqwest::header::{HeaderMap, HeaderValue};
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RANGE};
 use reqwest::{Body, Method, Url};
+use ring::rand::{SecureRandom, SystemRandom};
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
 use std::mem;
@@ -86,52 +88,201 @@ impl Query for CommonObjectRequestParams {
     }
 }
 
-impl Query for InsertObjectRequest {
+pub(crate) fn upload_url(base_url: Url, bucket: &str) -> Result<Url> {
+    base_url
+        .join("/upload/storage/v1/")?
+        .bucket(bucket)?
+        .join_segment("o")
+}
+
+impl Query for InsertObjectSpec {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
-        // obviously this needs some work
-        let insert_object_spec = match self.first_message.as_mut().unwrap() {
-            FirstMessage::UploadId(_) => panic!(),
-            FirstMessage::InsertObjectSpec(spec) => spec,
-        };
+        let mut query = Vec::new();
+
+        push_if_opt!(self, query, if_generation_match);
+        push_if_opt!(self, query, if_generation_not_match);
+        push_if_opt!(self, query, if_metageneration_match);
+        push_if_opt!(self, query, if_metageneration_not_match);
+
+        push_enum!(self, query, PredefinedObjectAcl, predefined_acl);
+
+        push_enum!(self, query, Projection, projection);
+
+        query
+    }
+}
+
+/// Separates the parts of a `multipart/related` upload, unique to each request so it
+/// cannot occur in the object data by accident
+pub(crate) fn multipart_boundary() -> Result<String> {
+    let mut random = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut random)
+        .map_err(|_| other_error("Failed to generate a multipart boundary".into()))?;
+
+    Ok(random.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The parts of a `multipart/related` body before and after the object data, the first part is
+/// the JSON resource
+fn multipart_head_tail(boundary: &str, resource: &Object) -> Result<(Bytes, Bytes)> {
+    let content_type = if resource.content_type.is_empty() {
+        "application/octet-stream"
+    } else {
+        &resource.content_type
+    };
+
+    let mut head = format!(
+        "--{}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n",
+        boundary
+    )
+    .into_bytes();
+    serde_json::to_writer(&mut head, resource)?;
+    head.extend_from_slice(
+        format!(
+            "\r\n--{}\r\nContent-Type: {}\r\n\r\n",
+            boundary, content_type
+        )
+        .as_bytes(),
+    );
+
+    let tail = format!("\r\n--{}--\r\n", boundary);
+
+    Ok((Bytes::from(head), Bytes::from(tail)))
+}
+
+/// A multipart body for data already in memory, which can be sent again when the request is
+/// retried
+fn multipart_body(boundary: &str, resource: &Object, content: &[u8]) -> Result<Vec<u8>> {
+    let (head, tail) = multipart_head_tail(boundary, resource)?;
+
+    let mut body = Vec::with_capacity(head.len() + content.len() + tail.len());
+    body.extend_from_slice(&head);
+    body.extend_from_slice(content);
+    body.extend_from_slice(&tail);
 
-        let mut resource = insert_object_spec.resource.take().unwrap();
+    Ok(body)
+}
+
+/// A multipart body that streams the object data
+fn multipart_stream<S>(
+    boundary: &str,
+    resource: &Object,
+    bytes: S,
+) -> Result<impl Stream<Item = Result<Bytes>> + Send + Sync + 'static>
+where
+    S: Stream<Item = Result<Bytes>> + Send + Sync + 'static,
+{
+    let (head, tail) = multipart_head_tail(boundary, resource)?;
+
+    Ok(stream::once(future::ok(head))
+        .chain(bytes)
+        .chain(stream::once(future::ok(tail))))
+}
 
+impl Query for InsertObjectRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
         let mut query = self.common_request_params.request_query();
         query.extend(self.common_object_request_params.request_query());
-        query.push(("uploadType", "media".to_string()));
-        push_if!(resource, query, name);
+        query.push(("uploadType", "multipart".to_string()));
+
+        if let Some(FirstMessage::InsertObjectSpec(insert_object_spec)) =
+            self.first_message.as_mut()
+        {
+            query.extend(insert_object_spec.request_query());
+        }
+
         query
     }
 }
 
-impl Request for InsertObjectRequest {
+/// An `InsertObjectRequest` sent as a `multipart/related` upload
+#[derive(Debug)]
+struct MultipartUploadRequest {
+    request: InsertObjectRequest,
+    boundary: String,
+}
+
+impl MultipartUploadRequest {
+    fn new(request: InsertObjectRequest) -> Result<Self> {
+        Ok(MultipartUploadRequest {
+            request,
+            boundary: multipart_boundary()?,
+        })
+    }
+}
+
+impl Query for MultipartUploadRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.request.request_query()
+    }
+}
+
+impl Request for MultipartUploadRequest {
     const REQUEST_METHOD: Method = Method::POST;
 
     type Response = Object;
 
-    fn request_path(&self, base_url: Url) -> Result<Url> {
-        let insert_object_spec = match self.first_message.as_ref().unwrap() {
-            FirstMessage::UploadId(_) => panic!(),
-            FirstMessage::InsertObjectSpec(spec) => spec,
-        };
+    fn is_idempotent(&self) -> bool {
+        match self.request.first_message.as_ref() {
+            Some(FirstMessage::InsertObjectSpec(spec)) => spec.if_generation_match.is_some(),
+            _ => false,
+        }
+    }
 
-        let resource = insert_object_spec.resource.as_ref().unwrap();
-        let base_url = base_url.join("/upload/storage/v1/")?;
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        let resource = spec_resource(&self.request)?;
 
-        base_url.bucket(&resource.bucket)?.join_segment("o")
+        upload_url(base_url, &resource.bucket)
     }
 
     fn request_headers(&self) -> HeaderMap<HeaderValue> {
-        unimplemented!()
+        let mut headers = HeaderMap::with_capacity(1);
+        if let Ok(content_type) = format!("multipart/related; boundary={}", self.boundary).parse() {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+        headers
+    }
+}
+
+/// The object resource in the spec of an insert request
+fn spec_resource(request: &InsertObjectRequest) -> Result<&Object> {
+    match request.first_message.as_ref() {
+        Some(FirstMessage::InsertObjectSpec(spec)) => spec.resource.as_ref(),
+        _ => None,
+    }
+    .ok_or_else(|| other_error("Expected first_message.insert_object_spec.resource field".into()))
+}
+
+/// The resource sent in the metadata part of a multipart upload, including any checksums
+fn insert_resource(request: &mut InsertObjectRequest) -> Result<Object> {
+    let mut resource = spec_resource(request)?.clone();
+
+    if let Some(checksums) = request.object_checksums.take() {
+        resource.crc32c = checksums.crc32c;
+        resource.md5_hash = checksums.md5_hash;
     }
+
+    // the bucket is taken from the request path
+    resource.bucket.clear();
+
+    Ok(resource)
 }
 
 impl Query for GetObjectRequest {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
         let mut query = self.common_request_params.request_query();
         query.extend(self.common_object_request_params.request_query());
+
+        push_if!(self, query, generation);
+        push_if_opt!(self, query, if_generation_match);
+        push_if_opt!(self, query, if_generation_not_match);
+        push_if_opt!(self, query, if_metageneration_match);
+        push_if_opt!(self, query, if_metageneration_not_match);
+
         push_enum!(self, query, Projection, projection);
-        unimplemented!()
+
+        query
     }
 }
 
@@ -180,12 +331,7 @@ impl Query for ComposeObjectRequest {
         push_if_opt!(self, query, if_metageneration_match);
         push_if_opt!(self, query, if_generation_match);
 
-        query.extend(
-            PredefinedObjectAcl::from_i32(mem::take(&mut self.destination_predefined_acl))
-                .request_query()
-                .into_iter()
-                .map(|(_, v)| ("destinationPredefinedAcl", v)),
-        );
+        push_enum!(self, query, PredefinedObjectAcl, destination_predefined_acl);
 
         push_if!(self, query, kms_key_name);
 
@@ -198,6 +344,10 @@ impl Request for ComposeObjectRequest {
 
     type Response = Object;
 
+    fn is_idempotent(&self) -> bool {
+        self.if_generation_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url
             .bucket(&self.destination_bucket)?
@@ -213,12 +363,7 @@ impl Query for CopyObjectRequest {
 
         push_if!(self, query, destination_kms_key_name);
 
-        query.extend(
-            PredefinedObjectAcl::from_i32(mem::take(&mut self.destination_predefined_acl))
-                .request_query()
-                .into_iter()
-                .map(|(_, v)| (constants::destination_predefined_acl, v)),
-        );
+        push_enum!(self, query, PredefinedObjectAcl, destination_predefined_acl);
 
         push_if_opt!(self, query, if_generation_match);
         push_if_opt!(self, query, if_generation_not_match);
@@ -243,6 +388,10 @@ impl Request for CopyObjectRequest {
 
     type Response = Object;
 
+    fn is_idempotent(&self) -> bool {
+        self.if_generation_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url
             .bucket(&self.source_bucket)?
@@ -260,12 +409,7 @@ impl Query for RewriteObjectRequest {
 
         push_if!(self, query, destination_kms_key_name);
 
-        query.extend(
-            PredefinedObjectAcl::from_i32(mem::take(&mut self.destination_predefined_acl))
-                .request_query()
-                .into_iter()
-                .map(|(_, v)| (constants::destination_predefined_acl, v)),
-        );
+        push_enum!(self, query, PredefinedObjectAcl, destination_predefined_acl);
 
         push_if_opt!(self, query, if_generation_match);
         push_if_opt!(self, query, if_generation_not_match);
@@ -293,6 +437,10 @@ impl Request for RewriteObjectRequest {
 
     type Response = RewriteResponse;
 
+    fn is_idempotent(&self) -> bool {
+        self.if_generation_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url
             .bucket(&self.source_bucket)?
@@ -330,6 +478,23 @@ impl Request for GetObjectMediaRequest {
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url.bucket(&self.bucket)?.object(&self.object)
     }
+
+    fn request_headers(&self) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::with_capacity(1);
+
+        // a negative offset reads from the end of the object, a zero limit reads to the end
+        let range = match (self.read_offset, self.read_limit) {
+            (0, 0) => None,
+            (offset, _) if offset < 0 => Some(format!("bytes={}", offset)),
+            (offset, 0) => Some(format!("bytes={}-", offset)),
+            (offset, limit) => Some(format!("bytes={}-{}", offset, offset + limit - 1)),
+        };
+
+        if let Some(Ok(range)) = range.map(|range| range.parse()) {
+            headers.insert(RANGE, range);
+        }
+        headers
+    }
 }
 
 impl From<Object> for GetObjectMediaRequest {
@@ -365,6 +530,7 @@ impl Query for DeleteObjectRequest {
         let mut query = self.common_request_params.request_query();
         query.extend(self.common_object_request_params.request_query());
 
+        push_if!(self, query, generation);
         push_if_opt!(self, query, if_generation_match);
         push_if_opt!(self, query, if_generation_not_match);
         push_if_opt!(self, query, if_metageneration_match);
@@ -379,6 +545,11 @@ impl Request for DeleteObjectRequest {
 
     type Response = ();
 
+    /// Deleting a specific generation can only ever remove that generation
+    fn is_idempotent(&self) -> bool {
+        self.generation != 0 || self.if_generation_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url.bucket(&self.bucket)?.object(&self.object)
     }
@@ -414,7 +585,20 @@ impl FromStr for DeleteObjectRequest {
 
 impl Query for UpdateObjectRequest {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
-        unimplemented!()
+        let mut query = self.common_request_params.request_query();
+        query.extend(self.common_object_request_params.request_query());
+
+        push_if!(self, query, generation);
+        push_if_opt!(self, query, if_generation_match);
+        push_if_opt!(self, query, if_generation_not_match);
+        push_if_opt!(self, query, if_metageneration_match);
+        push_if_opt!(self, query, if_metageneration_not_match);
+
+        push_enum!(self, query, PredefinedObjectAcl, predefined_acl);
+
+        push_enum!(self, query, Projection, projection);
+
+        query
     }
 }
 
@@ -423,6 +607,43 @@ impl Request for UpdateObjectRequest {
 
     type Response = Object;
 
+    fn is_idempotent(&self) -> bool {
+        self.if_metageneration_match.is_some()
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url.bucket(&self.bucket)?.object(&self.object)
+    }
+}
+
+impl Query for PatchObjectRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+        query.extend(self.common_object_request_params.request_query());
+
+        push_if!(self, query, generation);
+        push_if_opt!(self, query, if_generation_match);
+        push_if_opt!(self, query, if_generation_not_match);
+        push_if_opt!(self, query, if_metageneration_match);
+        push_if_opt!(self, query, if_metageneration_not_match);
+
+        push_enum!(self, query, PredefinedObjectAcl, predefined_acl);
+
+        push_enum!(self, query, Projection, projection);
+
+        query
+    }
+}
+
+impl Request for PatchObjectRequest {
+    const REQUEST_METHOD: Method = Method::PATCH;
+
+    type Response = Object;
+
+    fn is_idempotent(&self) -> bool {
+        self.if_metageneration_match.is_some()
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url> {
         base_url.bucket(&self.bucket)?.object(&self.object)
     }
@@ -538,14 +759,76 @@ impl Client {
     #[doc = " `true` will cause an error. The client **should** check the"]
     #[doc = " `Object` it receives to determine how much data the service was"]
     #[doc = " able to commit and whether the service views the object as complete."]
+    #[doc = ""]
+    #[doc = " A resumable write returns an `Object` with only `size` set, the number of"]
+    #[doc = " bytes committed, until the write is finished. Otherwise the object and its"]
+    #[doc = " metadata are created in a single multipart upload, with the CRC32C and MD5"]
+    #[doc = " of the data unless `object_checksums` is set."]
     #[tracing::instrument]
     pub async fn insert_object(
         &self,
-        _request: impl Into<InsertObjectRequest> + Debug,
+        request: impl Into<InsertObjectRequest> + Debug,
     ) -> crate::Result<Object> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let content = match request.data.take() {
+            None => Bytes::new(),
+            Some(Data::ChecksummedData(data)) => Bytes::from(data.content),
+            Some(Data::Reference(_)) => {
+                return Err(other_error(
+                    "Writing from a reference is not supported".into(),
+                ))
+            }
+        };
+
+        match request.first_message {
+            Some(FirstMessage::UploadId(ref upload_id)) => {
+                match self
+                    .write_chunk_resumable(
+                        upload_id,
+                        request.write_offset,
+                        content,
+                        request.finish_write,
+                        None,
+                        request.common_object_request_params,
+                    )
+                    .await?
+                {
+                    WriteStatus::Complete(object) => Ok(*object),
+                    WriteStatus::Incomplete { committed_size } => Ok(Object {
+                        size: committed_size,
+                        ..Default::default()
+                    }),
+                }
+            }
+            _ => {
+                let mut hasher = checksum::Hasher::default();
+                hasher.update(&content);
+                let computed = hasher.finish();
+
+                if request.object_checksums.is_none() {
+                    request.object_checksums = Some(computed.object_checksums());
+                }
+
+                let resource = insert_resource(&mut request)?;
+                let request = MultipartUploadRequest::new(request)?;
+                let body = multipart_body(&request.boundary, &resource, &content)?;
+
+                let object = self.invoke_body(request, body).await?;
+
+                self.verify_upload(object, &computed).await
+            }
+        }
     }
 
+    #[doc = " Stores a new object and metadata in a single multipart upload, every"]
+    #[doc = " field of `request.resource` is sent along with the data."]
+    #[doc = ""]
+    #[doc = " The metadata part is sent before the data, so the service can only"]
+    #[doc = " check `object_checksums` supplied by the caller. The CRC32C and MD5"]
+    #[doc = " computed while streaming are checked against the stored object after"]
+    #[doc = " the upload, use `insert_object_resumable` to have the service reject"]
+    #[doc = " corrupt data before the object is created."]
     #[tracing::instrument(skip(bytes))]
     pub async fn insert_object_stream<S>(
         &self,
@@ -558,9 +841,10 @@ impl Client {
     where
         S: Stream<Item = Bytes> + Send + Sync + 'static,
     {
-        let bytes = bytes.into().map::<crate::Result<Bytes>, _>(Ok);
+        // the metadata is sent before the data, so the hashes can only be checked afterwards
+        let (bytes, hashes) = checksum::hashed_stream(bytes.into());
 
-        let request = InsertObjectRequest {
+        let mut request = InsertObjectRequest {
             object_checksums,
             common_object_request_params,
             common_request_params,
@@ -568,7 +852,17 @@ impl Client {
             ..Default::default()
         };
 
-        self.invoke_body(request, Body::wrap_stream(bytes)).await
+        let resource = insert_resource(&mut request)?;
+        let request = MultipartUploadRequest::new(request)?;
+        let body = multipart_stream(&request.boundary, &resource, bytes)?;
+
+        let object = self.invoke_body(request, Body::wrap_stream(body)).await?;
+
+        let computed = hashes.await.map_err(|_| {
+            other_error("The upload finished before all its data was hashed".into())
+        })?;
+
+        self.verify_upload(object, &computed).await
     }
 
     #[doc = " Retrieves a list of objects matching the criteria."]
@@ -616,6 +910,9 @@ impl Client {
     }
 
     #[doc = " Reads an object's data."]
+    #[doc = ""]
+    #[doc = " The data is checked against the CRC32C and MD5 reported by the service,"]
+    #[doc = " except for range reads and decompressive transcoding."]
     #[tracing::instrument]
     pub async fn get_object_media_bytes(
         &self,
@@ -623,10 +920,15 @@ impl Client {
     ) -> Result<Vec<u8>> {
         let request = request.into();
 
-        Ok(self.get(request).await?.bytes().await?.to_vec())
+        Ok(checksum::validated_bytes(self.get(request).await?)
+            .await?
+            .to_vec())
     }
 
     #[doc = " Reads an object's data."]
+    #[doc = ""]
+    #[doc = " The data is checked against the CRC32C and MD5 reported by the service"]
+    #[doc = " once the stream ends, except for range reads and decompressive transcoding."]
     #[tracing::instrument]
     pub async fn get_object_media_stream(
         &self,
@@ -634,19 +936,26 @@ impl Client {
     ) -> crate::Result<impl Stream<Item = crate::Result<Bytes>> + Unpin> {
         let request = request.into();
 
-        Ok(self
-            .get(request)
-            .await?
-            .bytes_stream()
-            .map_err(|e| e.into()))
+        Ok(checksum::validated_stream(self.get(request).await?))
     }
 
     #[doc = " Updates an object's metadata."]
+    #[doc = ""]
+    #[doc = " Only the fields named in `update_mask` are sent, masked fields left at"]
+    #[doc = " their default value are cleared. Without a mask every non-default field"]
+    #[doc = " of `metadata` is sent."]
+    #[tracing::instrument]
     pub async fn patch_object(
-        &mut self,
-        _request: impl Into<PatchObjectRequest>,
+        &self,
+        request: impl Into<PatchObjectRequest> + Debug,
     ) -> Result<Object> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let metadata = request.metadata.take().unwrap_or_default();
+        let body = field_mask::patch_body(&metadata, request.update_mask.as_ref())?;
+        drop(metadata);
+
+        self.invoke_json(request, body).await
     }
 
     #[doc = " Updates an object's metadata. Equivalent to PatchObject, but always"]
@@ -674,7 +983,10 @@ impl Client {
     ) -> Result<()> {
         let request = request.into();
 
-        self.invoke(request).await
+        // a successful delete has an empty body
+        self.get(request).await?;
+
+        Ok(())
     }
 
     #[doc = " Concatenates a list of existing objects into a new object in the same"]
@@ -734,36 +1046,4 @@ impl Client {
 
         self.invoke_json(request, object).await
     }
-
-    #[doc = " Starts a resumable write. How long the write operation remains valid, and"]
-    #[doc = " what happens when the write operation becomes invalid, are"]
-    #[doc = " service-dependent."]
-    #[tracing::instrument]
-    pub async fn start_resumable_write(
-        &self,
-        _request: impl Into<StartResumableWriteRequest> + Debug,
-    ) -> Result<StartResumableWriteResponse> {
-        unimplemented!()
-    }
-
-    #[doc = " Determines the `committed_size` for an object that is being written, which"]
-    #[doc = " can then be used as the `write_offset` for the next `Write()` call."]
-    #[doc = ""]
-    #[doc = " If the object does not exist (i.e., the object has been deleted, or the"]
-    #[doc = " first `Write()` has not yet reached the service), this method returns the"]
-    #[doc = " error `NOT_FOUND`."]
-    #[doc = ""]
-    #[doc = " The client **may** call `QueryWriteStatus()` at any time to determine how"]
-    #[doc = " much data has been processed for this object. This is useful if the"]
-    #[doc = " client is buffering data and needs to know which data can be safely"]
-    #[doc = " evicted. For any sequence of `QueryWriteStatus()` calls for a given"]
-    #[doc = " object name, the sequence of returned `committed_size` values will be"]
-    #[doc = " non-decreasing."]
-    #[tracing::instrument]
-    pub async fn query_write_status(
-        &mut self,
-        _request: impl Into<QueryWriteStatusRequest> + Debug,
-    ) -> Result<QueryWriteStatusResponse> {
-        unimplemented!()
-    }
 }
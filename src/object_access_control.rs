@@ -1,3 +1,4 @@
+use crate::field_mask;
 use crate::google::storage::v1::{
     DeleteObjectAccessControlRequest, GetObjectAccessControlRequest,
     InsertObjectAccessControlRequest, ListObjectAccessControlsRequest,
@@ -12,7 +13,10 @@ use reqwest::{Method, Url};
 use std::fmt::Debug;
 
 fn acl_url(base_url: Url, bucket: &str, object: &str) -> Result<Url> {
-    base_url.bucket(bucket)?.object(object)?.join_segment("acl")
+    base_url
+        .bucket(bucket)?
+        .object(object)?
+        .join_segment("acl/")
 }
 
 impl Query for InsertObjectAccessControlRequest {
@@ -41,7 +45,11 @@ impl Request for InsertObjectAccessControlRequest {
 
 impl Query for ListObjectAccessControlsRequest {
     fn request_query(&mut self) -> Vec<(&'static str, String)> {
-        unimplemented!()
+        let mut query = self.common_request_params.request_query();
+
+        push_if!(self, query, generation);
+
+        query
     }
 }
 
@@ -123,6 +131,30 @@ impl Request for DeleteObjectAccessControlRequest {
     }
 }
 
+impl Query for PatchObjectAccessControlRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+
+        push_if!(self, query, generation);
+
+        query
+    }
+}
+
+impl Request for PatchObjectAccessControlRequest {
+    const REQUEST_METHOD: Method = Method::PATCH;
+
+    type Response = ObjectAccessControl;
+
+    fn scope(&self) -> &'static str {
+        crate::request::Scope::FULL_CONTROL
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        Ok(acl_url(base_url, &self.bucket, &self.object)?.join(&self.entity)?)
+    }
+}
+
 impl Client {
     #[doc = " Creates a new ACL entry on the specified object."]
     #[tracing::instrument]
@@ -162,11 +194,18 @@ impl Client {
     #[doc = " Patches an ACL entry on the specified object.  Patch is similar to update,"]
     #[doc = " but only applies or appends the specified fields in the"]
     #[doc = " object_access_control object.  Other fields are unaffected."]
+    #[tracing::instrument]
     pub async fn patch_object_access_control(
-        &mut self,
-        _request: impl Into<PatchObjectAccessControlRequest>,
+        &self,
+        request: impl Into<PatchObjectAccessControlRequest> + Debug,
     ) -> Result<ObjectAccessControl> {
-        unimplemented!()
+        let mut request = request.into();
+
+        let object_access_control = request.object_access_control.take().unwrap_or_default();
+        let body = field_mask::patch_body(&object_access_control, request.update_mask.as_ref())?;
+        drop(object_access_control);
+
+        self.invoke_json(request, body).await
     }
 
     #[doc = " Updates an ACL entry on the specified object."]
@@ -0,0 +1,213 @@
+use crate::error::other_error;
+use crate::signed_url::{endpoint_host, hex, now, UrlStyle, MAX_EXPIRATION};
+use crate::signer::Signer;
+use crate::{encode, Result};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use url::Url;
+
+/// Escape non-ASCII characters in the policy document as `\uXXXX`
+fn escape_non_ascii(json: &str) -> String {
+    let mut output = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c.is_ascii() {
+            output.push(c);
+        } else {
+            let mut utf16 = [0; 2];
+            for unit in c.encode_utf16(&mut utf16) {
+                output.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    output
+}
+
+/// A V4 POST policy for uploads from HTML forms,
+/// see https://cloud.google.com/storage/docs/xml-api/post-object-forms
+#[derive(Clone, Debug)]
+pub struct PostPolicyV4 {
+    bucket: String,
+    object: String,
+    key_prefix: Option<String>,
+    expires_in: Duration,
+    conditions: Vec<Value>,
+    fields: BTreeMap<String, String>,
+    style: UrlStyle,
+    endpoint: Option<Url>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+/// The URL a form posts to and the fields it must include, the file goes in a final `file` field
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostPolicy {
+    pub url: Url,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl PostPolicyV4 {
+    /// A policy for uploading `object`, which can contain `${filename}` to use the name of the
+    /// uploaded file
+    pub fn new(bucket: impl Into<String>, object: impl Into<String>) -> Self {
+        PostPolicyV4 {
+            bucket: bucket.into(),
+            object: object.into(),
+            key_prefix: None,
+            expires_in: Duration::from_secs(60 * 60),
+            conditions: vec![],
+            fields: Default::default(),
+            style: Default::default(),
+            endpoint: None,
+            timestamp: None,
+        }
+    }
+
+    /// How long the policy is valid for, at most 7 days. Defaults to one hour.
+    pub fn expires_in(mut self, expires_in: Duration) -> Self {
+        self.expires_in = expires_in;
+        self
+    }
+
+    /// Allow any object name that starts with `prefix`, instead of only the exact name
+    pub fn key_starts_with(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Limit the size of the uploaded file, in bytes
+    pub fn content_length_range(mut self, min: u64, max: u64) -> Self {
+        self.conditions
+            .push(json!(["content-length-range", min, max]));
+        self
+    }
+
+    /// Require a form field to start with `prefix`
+    pub fn starts_with(mut self, field: impl AsRef<str>, prefix: impl Into<String>) -> Self {
+        self.conditions.push(json!([
+            "starts-with",
+            format!("${}", field.as_ref()),
+            prefix.into()
+        ]));
+        self
+    }
+
+    /// Require a form field to have exactly this value, it is added to the returned fields
+    pub fn field(mut self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        let (field, value) = (field.into(), value.into());
+        self.conditions
+            .push(json!({ field.clone(): value.clone() }));
+        self.fields.insert(field, value);
+        self
+    }
+
+    pub fn content_type(self, content_type: impl Into<String>) -> Self {
+        self.field("Content-Type", content_type)
+    }
+
+    /// A predefined ACL such as `public-read`
+    pub fn acl(self, acl: impl Into<String>) -> Self {
+        self.field("acl", acl)
+    }
+
+    /// Where the browser is redirected after a successful upload
+    pub fn success_action_redirect(self, url: impl Into<String>) -> Self {
+        self.field("success_action_redirect", url)
+    }
+
+    /// The status code returned after a successful upload, when there is no redirect
+    pub fn success_action_status(self, status: u16) -> Self {
+        self.field("success_action_status", status.to_string())
+    }
+
+    /// Custom metadata, stored as `x-goog-meta-{name}`
+    pub fn metadata(self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+        self.field(format!("x-goog-meta-{}", name.as_ref()), value)
+    }
+
+    pub fn style(mut self, style: UrlStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// The scheme and host the form posts to, defaults to `https://storage.googleapis.com`
+    pub fn endpoint(mut self, endpoint: impl Into<Url>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// The time the policy becomes valid, defaults to now
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sign the policy with a service account key, an HMAC key or the IAM `signBlob` API
+    pub async fn sign<S: Signer + ?Sized>(&self, signer: &S) -> Result<PostPolicy> {
+        if self.expires_in > MAX_EXPIRATION {
+            return Err(other_error(
+                "POST policies expire after at most 7 days".into(),
+            ));
+        }
+
+        let timestamp = self.timestamp.unwrap_or_else(now);
+        let expiration = timestamp + chrono::Duration::seconds(self.expires_in.as_secs() as i64);
+
+        let date = timestamp.format("%Y%m%d").to_string();
+        let datetime = timestamp.format("%Y%m%dT%H%M%SZ").to_string();
+        let credential = format!(
+            "{}/{}/auto/storage/goog4_request",
+            signer.client_email(),
+            date
+        );
+
+        let mut fields = self.fields.clone();
+        fields.insert("key".to_string(), self.object.clone());
+        fields.insert(
+            "x-goog-algorithm".to_string(),
+            signer.algorithm().to_string(),
+        );
+        fields.insert("x-goog-credential".to_string(), credential.clone());
+        fields.insert("x-goog-date".to_string(), datetime.clone());
+
+        let mut conditions = self.conditions.clone();
+        conditions.push(json!({ "bucket": self.bucket }));
+        conditions.push(match &self.key_prefix {
+            Some(prefix) => json!(["starts-with", "$key", prefix]),
+            None => json!({ "key": self.object }),
+        });
+        conditions.push(json!({ "x-goog-date": datetime }));
+        conditions.push(json!({ "x-goog-credential": credential }));
+        conditions.push(json!({ "x-goog-algorithm": signer.algorithm() }));
+
+        let policy = json!({
+            "conditions": conditions,
+            "expiration": expiration.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        });
+
+        let policy = base64::encode(escape_non_ascii(&serde_json::to_string(&policy)?));
+        let signature = hex(signer.sign(&date, policy.as_bytes()).await?);
+
+        fields.insert("policy".to_string(), policy);
+        fields.insert("x-goog-signature".to_string(), signature);
+
+        let (endpoint, host) = endpoint_host(self.endpoint.as_ref())?;
+        let url = match self.style {
+            UrlStyle::PathStyle => format!(
+                "{}://{}/{}/",
+                endpoint.scheme(),
+                host,
+                encode::slash(&self.bucket)
+            ),
+            UrlStyle::VirtualHostedStyle => {
+                format!("{}://{}.{}/", endpoint.scheme(), self.bucket, host)
+            }
+            UrlStyle::BucketBoundHostname => format!("{}://{}/", endpoint.scheme(), host),
+        };
+
+        Ok(PostPolicy {
+            url: Url::parse(&url)?,
+            fields,
+        })
+    }
+}
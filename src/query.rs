@@ -3,7 +3,6 @@ use crate::google::storage::v1::common_enums::{
 };
 use crate::google::storage::v1::CommonRequestParams;
 use crate::push_if;
-use std::mem;
 
 pub(crate) trait Query {
     fn request_query(&mut self) -> Vec<(&'static str, String)>;
@@ -32,29 +31,29 @@ impl Query for CommonRequestParams {
     }
 }
 
-impl Query for PredefinedBucketAcl {
-    fn request_query(&mut self) -> Vec<(&'static str, String)> {
-        use PredefinedBucketAcl::*;
+/// An enum sent as the value of the query parameter named after its request field
+pub(crate) trait QueryValue {
+    fn query_value(&self) -> Option<&'static str>;
+}
 
-        if let Some(predefined_acl) = match mem::take(self) {
+impl QueryValue for PredefinedBucketAcl {
+    fn query_value(&self) -> Option<&'static str> {
+        use PredefinedBucketAcl::*;
+        match self {
             Unspecified => None,
             BucketAclAuthenticatedRead => Some("authenticatedRead"),
             BucketAclPrivate => Some("private"),
             BucketAclProjectPrivate => Some("projectPrivate"),
             BucketAclPublicRead => Some("publicRead"),
             BucketAclPublicReadWrite => Some("publicReadWrite"),
-        } {
-            vec![("predefinedAcl", predefined_acl.to_string())]
-        } else {
-            vec![]
         }
     }
 }
 
-impl Query for PredefinedObjectAcl {
-    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+impl QueryValue for PredefinedObjectAcl {
+    fn query_value(&self) -> Option<&'static str> {
         use PredefinedObjectAcl::*;
-        if let Some(predefined_default_object_acl) = match self {
+        match self {
             Unspecified => None,
             ObjectAclAuthenticatedRead => Some("authenticatedRead"),
             ObjectAclBucketOwnerFullControl => Some("bucketOwnerFullControl"),
@@ -62,30 +61,17 @@ impl Query for PredefinedObjectAcl {
             ObjectAclPrivate => Some("private"),
             ObjectAclProjectPrivate => Some("projectPrivate"),
             ObjectAclPublicRead => Some("publicRead"),
-        } {
-            *self = Unspecified;
-            vec![(
-                "predefinedDefaultObjectAcl",
-                predefined_default_object_acl.to_string(),
-            )]
-        } else {
-            vec![]
         }
     }
 }
 
-impl Query for Projection {
-    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+impl QueryValue for Projection {
+    fn query_value(&self) -> Option<&'static str> {
         use Projection::*;
-        if let Some(projection) = match self {
+        match self {
             Unspecified => None,
             NoAcl => Some("noAcl"),
             Full => Some("full"),
-        } {
-            *self = Unspecified;
-            vec![("projection", projection.to_string())]
-        } else {
-            vec![]
         }
     }
 }
@@ -103,9 +89,9 @@ impl<T: Default + PartialEq + ToString> PushIf<T> for Vec<(&'static str, String)
     }
 
     fn push_if_opt(&mut self, key: &'static str, value: &mut Option<T>) {
-        match value.take().as_mut() {
-            Some(value) => self.push_if(key, value),
-            None => {}
+        // an explicit default is meaningful, e.g. `ifGenerationMatch=0` only creates new objects
+        if let Some(value) = value.take() {
+            self.push((key, value.to_string()));
         }
     }
 }
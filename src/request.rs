@@ -31,6 +31,12 @@ pub(crate) trait Request: Query {
         }
     }
 
+    /// Whether sending this request more than once has the same effect as sending it once,
+    /// which makes it safe to retry. Mutations are only idempotent when they carry a precondition.
+    fn is_idempotent(&self) -> bool {
+        Self::REQUEST_METHOD == Method::GET
+    }
+
     fn request_path(&self, base_url: Url) -> Result<Url>;
 
     fn request_headers(&self) -> HeaderMap {
@@ -0,0 +1,686 @@
+use crate::checksum::{Hashes, Md5};
+use crate::error::other_error;
+use crate::google::storage::v1::{
+    CommonObjectRequestParams, CommonRequestParams, InsertObjectSpec, Object, ObjectChecksums,
+    QueryWriteStatusRequest, QueryWriteStatusResponse, StartResumableWriteRequest,
+    StartResumableWriteResponse,
+};
+use crate::object::upload_url;
+use crate::query::Query;
+use crate::request::Request;
+use crate::{Client, Result};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_RANGE, LOCATION, RANGE};
+use reqwest::{Method, Response, StatusCode, Url};
+use std::fmt::Debug;
+use std::time::Instant;
+
+/// Every chunk except the last must be a multiple of this size
+const CHUNK_SIZE_MULTIPLE: usize = 256 * 1024;
+
+/// Amount of data sent in each request of a resumable upload
+const DEFAULT_CHUNK_SIZE: usize = 32 * CHUNK_SIZE_MULTIPLE;
+
+/// Number of consecutive attempts that commit no new data before a chunk write gives up
+const MAX_RESUME_ATTEMPTS: usize = 3;
+
+/// The session URI returned by `StartResumableWrite` doubles as the `upload_id`
+fn session_url(upload_id: &str) -> Result<Url> {
+    Ok(upload_id.parse::<Url>()?)
+}
+
+impl Query for StartResumableWriteRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+        query.extend(self.common_object_request_params.request_query());
+        query.push(("uploadType", "resumable".to_string()));
+        query.extend(self.insert_object_spec.request_query());
+        query
+    }
+}
+
+impl Request for StartResumableWriteRequest {
+    const REQUEST_METHOD: Method = Method::POST;
+
+    type Response = StartResumableWriteResponse;
+
+    /// Opening a session does not create the object, an unused session expires
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        let resource = self
+            .insert_object_spec
+            .as_ref()
+            .and_then(|spec| spec.resource.as_ref())
+            .ok_or_else(|| other_error("Expected insert_object_spec.resource field".into()))?;
+
+        upload_url(base_url, &resource.bucket)
+    }
+
+    fn request_headers(&self) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::new();
+
+        let content_type = self
+            .insert_object_spec
+            .as_ref()
+            .and_then(|spec| spec.resource.as_ref())
+            .map(|resource| resource.content_type.as_str())
+            .filter(|content_type| !content_type.is_empty())
+            .and_then(|content_type| content_type.parse().ok());
+
+        if let Some(content_type) = content_type {
+            headers.insert("x-upload-content-type", content_type);
+        }
+
+        headers
+    }
+}
+
+impl Query for QueryWriteStatusRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        let mut query = self.common_request_params.request_query();
+        query.extend(self.common_object_request_params.request_query());
+        query
+    }
+}
+
+impl Request for QueryWriteStatusRequest {
+    const REQUEST_METHOD: Method = Method::PUT;
+
+    type Response = QueryWriteStatusResponse;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn request_path(&self, _base_url: Url) -> Result<Url> {
+        session_url(&self.upload_id)
+    }
+
+    fn request_headers(&self) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::with_capacity(1);
+        headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes */*"));
+        headers
+    }
+}
+
+/// A single `PUT` of object data to a resumable upload session
+#[derive(Debug)]
+pub(crate) struct WriteChunkRequest {
+    pub(crate) upload_id: String,
+    pub(crate) write_offset: i64,
+    pub(crate) length: i64,
+    pub(crate) finish_write: bool,
+    /// The hashes of the whole object, sent with the request that finishes the write
+    pub(crate) hashes: Option<Hashes>,
+    pub(crate) common_object_request_params: Option<CommonObjectRequestParams>,
+}
+
+impl WriteChunkRequest {
+    fn content_range(&self) -> String {
+        let end = self.write_offset + self.length;
+
+        let total = if self.finish_write {
+            end.to_string()
+        } else {
+            "*".to_string()
+        };
+
+        if self.length == 0 {
+            format!("bytes */{}", total)
+        } else {
+            format!("bytes {}-{}/{}", self.write_offset, end - 1, total)
+        }
+    }
+}
+
+impl Query for WriteChunkRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_object_request_params.request_query()
+    }
+}
+
+impl Request for WriteChunkRequest {
+    const REQUEST_METHOD: Method = Method::PUT;
+
+    type Response = Object;
+
+    fn request_path(&self, _base_url: Url) -> Result<Url> {
+        session_url(&self.upload_id)
+    }
+
+    fn request_headers(&self) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::with_capacity(2);
+        if let Ok(content_range) = self.content_range().parse() {
+            headers.insert(CONTENT_RANGE, content_range);
+        }
+        if let (true, Some(hashes)) = (self.finish_write, &self.hashes) {
+            if let Ok(hash) = hashes.header_value().parse() {
+                headers.insert("x-goog-hash", hash);
+            }
+        }
+        headers
+    }
+}
+
+/// A resumable upload session that can be persisted and continued in another process.
+///
+/// Customer-supplied encryption keys are not persisted and must be restored with
+/// `with_common_object_request_params` after deserializing.
+///
+/// Only the CRC32C of the committed bytes is persisted, it is the whole state of the checksum and
+/// can be extended after a restart. The MD5 is computed while the upload stays in the process
+/// that started it, but an MD5 in progress cannot be saved and restored, so uploads continued in
+/// another process are checked by CRC32C alone.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumableUpload {
+    upload_id: String,
+    committed_size: i64,
+    crc32c: u32,
+    #[serde(skip)]
+    md5: Option<Md5>,
+    insert_object_spec: InsertObjectSpec,
+    #[serde(skip)]
+    common_object_request_params: Option<CommonObjectRequestParams>,
+}
+
+impl ResumableUpload {
+    /// The session URI of the upload
+    pub fn upload_id(&self) -> &str {
+        &self.upload_id
+    }
+
+    /// The number of bytes the service has committed
+    pub fn committed_size(&self) -> i64 {
+        self.committed_size
+    }
+
+    /// The CRC32C of the committed bytes
+    pub fn crc32c(&self) -> u32 {
+        self.crc32c
+    }
+
+    /// The destination and preconditions the upload was started with
+    pub fn insert_object_spec(&self) -> &InsertObjectSpec {
+        &self.insert_object_spec
+    }
+
+    pub fn with_common_object_request_params(
+        mut self,
+        common_object_request_params: CommonObjectRequestParams,
+    ) -> Self {
+        self.common_object_request_params = Some(common_object_request_params);
+        self
+    }
+
+    /// The hashes of the committed bytes
+    fn hashes(&self) -> Hashes {
+        Hashes {
+            crc32c: Some(self.crc32c),
+            md5: self.md5.as_ref().map(Md5::digest),
+        }
+    }
+
+    fn commit(&mut self, data: &[u8]) {
+        self.crc32c = crc32c::crc32c_append(self.crc32c, data);
+        if let Some(md5) = self.md5.as_mut() {
+            md5.update(data);
+        }
+        self.committed_size += data.len() as i64;
+    }
+}
+
+/// The state of a resumable upload session as reported by the service
+#[derive(Debug)]
+pub(crate) enum WriteStatus {
+    Incomplete { committed_size: i64 },
+    Complete(Box<Object>),
+}
+
+impl WriteStatus {
+    async fn from_response(response: Response) -> Result<Self> {
+        if response.status() != StatusCode::PERMANENT_REDIRECT {
+            return Ok(WriteStatus::Complete(Box::new(
+                response.json::<Object>().await?,
+            )));
+        }
+
+        // the Range header is omitted when no bytes have been committed
+        let committed_size = match response.headers().get(RANGE) {
+            None => 0,
+            Some(range) => range
+                .to_str()
+                .ok()
+                .and_then(|range| range.strip_prefix("bytes="))
+                .and_then(|range| range.rsplit('-').next())
+                .and_then(|end| end.parse::<i64>().ok())
+                .map(|end| end + 1)
+                .ok_or_else(|| other_error(format!("Unexpected Range header {:?}", range)))?,
+        };
+
+        Ok(WriteStatus::Incomplete { committed_size })
+    }
+}
+
+impl Client {
+    pub(crate) async fn write_status(
+        &self,
+        request: QueryWriteStatusRequest,
+    ) -> Result<WriteStatus> {
+        let response = self.request_body(request, vec![]).await?;
+
+        WriteStatus::from_response(response).await
+    }
+
+    /// Chunks are sent once, `write_chunk_resumable` resumes from whatever the service committed
+    pub(crate) async fn write_chunk(
+        &self,
+        request: WriteChunkRequest,
+        data: Bytes,
+    ) -> Result<WriteStatus> {
+        let response = self.request_body_once(request, data).await?;
+
+        WriteStatus::from_response(response).await
+    }
+
+    /// Write `data` at `write_offset`, resuming from the committed offset when a request fails
+    /// or the service commits less than was sent.
+    ///
+    /// Transient failures of the write, and of the status query that follows, are retried with
+    /// the backoff and deadline of the client's retry policy, counted from the last time the
+    /// service committed more data.
+    pub(crate) async fn write_chunk_resumable(
+        &self,
+        upload_id: &str,
+        write_offset: i64,
+        data: Bytes,
+        finish_write: bool,
+        hashes: Option<Hashes>,
+        common_object_request_params: Option<CommonObjectRequestParams>,
+    ) -> Result<WriteStatus> {
+        let end = write_offset + data.len() as i64;
+        let mut committed_size = write_offset;
+        let mut stalled = 0;
+        let mut start = Instant::now();
+        let mut attempt = 1;
+        let mut query_status = false;
+
+        loop {
+            let remaining = data.slice((committed_size - write_offset) as usize..);
+
+            let result = if query_status {
+                let request = QueryWriteStatusRequest {
+                    upload_id: upload_id.to_string(),
+                    common_object_request_params: common_object_request_params.clone(),
+                    ..Default::default()
+                };
+
+                match self.request_body_once(request, vec![]).await {
+                    Ok(response) => WriteStatus::from_response(response).await,
+                    Err(err) => Err(err),
+                }
+            } else {
+                let request = WriteChunkRequest {
+                    upload_id: upload_id.to_string(),
+                    write_offset: committed_size,
+                    length: remaining.len() as i64,
+                    finish_write,
+                    hashes: hashes.clone(),
+                    common_object_request_params: common_object_request_params.clone(),
+                };
+
+                self.write_chunk(request, remaining).await
+            };
+
+            let status = match result {
+                Ok(status) => status,
+                // errors such as a failed precondition are returned, resending would not help
+                Err(err) => match self.retry_policy().retry_after(&err, attempt, start) {
+                    Some(backoff) => {
+                        tracing::debug!(error = %err, attempt, ?backoff, "resuming upload");
+
+                        tokio::time::delay_for(backoff).await;
+                        attempt += 1;
+                        query_status = true;
+                        continue;
+                    }
+                    None => return Err(err),
+                },
+            };
+
+            query_status = false;
+
+            match status {
+                WriteStatus::Incomplete {
+                    committed_size: size,
+                } if size < write_offset || size > end => {
+                    return Err(other_error(format!(
+                        "Service committed {} bytes, expected between {} and {}",
+                        size, write_offset, end
+                    )));
+                }
+                WriteStatus::Incomplete {
+                    committed_size: size,
+                } if size == end && !finish_write => return Ok(status),
+                WriteStatus::Incomplete {
+                    committed_size: size,
+                } => {
+                    if size > committed_size {
+                        stalled = 0;
+                        start = Instant::now();
+                        attempt = 1;
+                    } else if stalled < MAX_RESUME_ATTEMPTS {
+                        stalled += 1;
+                    } else {
+                        return Err(other_error(format!(
+                            "Upload {} made no progress past {} bytes after {} attempts",
+                            upload_id,
+                            size,
+                            stalled + 1
+                        )));
+                    }
+
+                    committed_size = size;
+                }
+                complete => return Ok(complete),
+            }
+        }
+    }
+
+    #[doc = " Starts a resumable write. How long the write operation remains valid, and"]
+    #[doc = " what happens when the write operation becomes invalid, are"]
+    #[doc = " service-dependent."]
+    #[doc = ""]
+    #[doc = " The returned `upload_id` is the session URI of the upload."]
+    #[tracing::instrument]
+    pub async fn start_resumable_write(
+        &self,
+        request: impl Into<StartResumableWriteRequest> + Debug,
+    ) -> Result<StartResumableWriteResponse> {
+        let request = request.into();
+
+        let resource = request
+            .insert_object_spec
+            .as_ref()
+            .and_then(|spec| spec.resource.clone());
+
+        let bytes = serde_json::to_vec(&resource)?;
+
+        let response = self.request_body(request, bytes).await?;
+
+        let upload_id = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(|| other_error("Expected Location header".into()))?
+            .to_string();
+
+        Ok(StartResumableWriteResponse { upload_id })
+    }
+
+    #[doc = " Determines the `committed_size` for an object that is being written, which"]
+    #[doc = " can then be used as the `write_offset` for the next `Write()` call."]
+    #[doc = ""]
+    #[doc = " If the object does not exist (i.e., the object has been deleted, or the"]
+    #[doc = " first `Write()` has not yet reached the service), this method returns the"]
+    #[doc = " error `NOT_FOUND`."]
+    #[doc = ""]
+    #[doc = " The client **may** call `QueryWriteStatus()` at any time to determine how"]
+    #[doc = " much data has been processed for this object. This is useful if the"]
+    #[doc = " client is buffering data and needs to know which data can be safely"]
+    #[doc = " evicted. For any sequence of `QueryWriteStatus()` calls for a given"]
+    #[doc = " object name, the sequence of returned `committed_size` values will be"]
+    #[doc = " non-decreasing."]
+    #[tracing::instrument]
+    pub async fn query_write_status(
+        &self,
+        request: impl Into<QueryWriteStatusRequest> + Debug,
+    ) -> Result<QueryWriteStatusResponse> {
+        let request = request.into();
+
+        Ok(match self.write_status(request).await? {
+            WriteStatus::Incomplete { committed_size } => QueryWriteStatusResponse {
+                committed_size,
+                complete: false,
+            },
+            WriteStatus::Complete(object) => QueryWriteStatusResponse {
+                committed_size: object.size,
+                complete: true,
+            },
+        })
+    }
+
+    #[doc = " Stores a new object and metadata using a resumable upload."]
+    #[doc = ""]
+    #[doc = " The stream is sent in chunks, and a chunk that fails is resumed from the"]
+    #[doc = " offset the service has committed."]
+    #[tracing::instrument(skip(bytes))]
+    pub async fn insert_object_resumable<S>(
+        &self,
+        request: InsertObjectSpec,
+        object_checksums: Option<ObjectChecksums>,
+        common_object_request_params: Option<CommonObjectRequestParams>,
+        common_request_params: Option<CommonRequestParams>,
+        bytes: S,
+    ) -> Result<Object>
+    where
+        S: Stream<Item = Bytes>,
+    {
+        let mut request = request;
+
+        if let (Some(resource), Some(checksums)) = (request.resource.as_mut(), object_checksums) {
+            resource.crc32c = checksums.crc32c;
+            resource.md5_hash = checksums.md5_hash;
+        }
+
+        let mut upload = self
+            .start_resumable_upload(StartResumableWriteRequest {
+                insert_object_spec: Some(request),
+                common_object_request_params,
+                common_request_params,
+            })
+            .await?;
+
+        self.upload_stream(&mut upload, 0, bytes).await
+    }
+
+    #[doc = " Starts a resumable upload that can be persisted and continued later,"]
+    #[doc = " possibly in another process."]
+    #[tracing::instrument]
+    pub async fn start_resumable_upload(
+        &self,
+        request: impl Into<StartResumableWriteRequest> + Debug,
+    ) -> Result<ResumableUpload> {
+        let request = request.into();
+
+        let insert_object_spec = request.insert_object_spec.clone().unwrap_or_default();
+        let common_object_request_params = request.common_object_request_params.clone();
+
+        let StartResumableWriteResponse { upload_id } = self.start_resumable_write(request).await?;
+
+        Ok(ResumableUpload {
+            upload_id,
+            committed_size: 0,
+            crc32c: 0,
+            md5: Some(Md5::default()),
+            insert_object_spec,
+            common_object_request_params,
+        })
+    }
+
+    #[doc = " Appends a chunk to a resumable upload at its `committed_size`. The chunk"]
+    #[doc = " length must be a multiple of 256 KiB."]
+    #[tracing::instrument(skip(data))]
+    pub async fn write_resumable_upload(
+        &self,
+        upload: &mut ResumableUpload,
+        data: Bytes,
+    ) -> Result<()> {
+        if data.len() % CHUNK_SIZE_MULTIPLE != 0 {
+            return Err(other_error(format!(
+                "Chunk length {} is not a multiple of {}",
+                data.len(),
+                CHUNK_SIZE_MULTIPLE
+            )));
+        }
+
+        match self
+            .write_chunk_resumable(
+                &upload.upload_id,
+                upload.committed_size,
+                data.clone(),
+                false,
+                None,
+                upload.common_object_request_params.clone(),
+            )
+            .await?
+        {
+            WriteStatus::Incomplete { .. } => {
+                upload.commit(&data);
+                Ok(())
+            }
+            WriteStatus::Complete(_) => Err(other_error(format!(
+                "Upload {} was finalized early",
+                upload.upload_id
+            ))),
+        }
+    }
+
+    #[doc = " Appends the final chunk to a resumable upload and finalizes the object."]
+    #[doc = ""]
+    #[doc = " The CRC32C of all the data written, and the MD5 when the upload was"]
+    #[doc = " started in this process, are sent with the final chunk and checked"]
+    #[doc = " against the stored object."]
+    #[tracing::instrument(skip(data))]
+    pub async fn finish_resumable_upload(
+        &self,
+        upload: &mut ResumableUpload,
+        data: Bytes,
+    ) -> Result<Object> {
+        let mut finished = upload.clone();
+        finished.commit(&data);
+        let hashes = finished.hashes();
+
+        match self
+            .write_chunk_resumable(
+                &upload.upload_id,
+                upload.committed_size,
+                data.clone(),
+                true,
+                Some(hashes.clone()),
+                upload.common_object_request_params.clone(),
+            )
+            .await?
+        {
+            WriteStatus::Complete(object) => {
+                *upload = finished;
+
+                self.verify_upload(*object, &hashes).await
+            }
+            WriteStatus::Incomplete { .. } => Err(other_error(format!(
+                "Upload {} was not finalized",
+                upload.upload_id
+            ))),
+        }
+    }
+
+    #[doc = " Continues a resumable upload, typically one rehydrated after a restart."]
+    #[doc = ""]
+    #[doc = " The stream must start at `upload.committed_size()`. Any bytes the service"]
+    #[doc = " committed after the upload was persisted are skipped. When the service"]
+    #[doc = " has already finalized the object, the rest of the stream is read to"]
+    #[doc = " check the object against the hashes of all the data."]
+    #[tracing::instrument(skip(bytes))]
+    pub async fn continue_resumable_upload<S>(
+        &self,
+        upload: &mut ResumableUpload,
+        bytes: S,
+    ) -> Result<Object>
+    where
+        S: Stream<Item = Bytes>,
+    {
+        let status = self
+            .write_status(QueryWriteStatusRequest {
+                upload_id: upload.upload_id.clone(),
+                common_object_request_params: upload.common_object_request_params.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        match status {
+            WriteStatus::Complete(object) => {
+                futures::pin_mut!(bytes);
+                while let Some(chunk) = bytes.next().await {
+                    upload.commit(&chunk);
+                }
+
+                self.verify_upload(*object, &upload.hashes()).await
+            }
+            WriteStatus::Incomplete { committed_size }
+                if committed_size < upload.committed_size =>
+            {
+                Err(other_error(format!(
+                    "Service committed {} bytes of upload {}, expected at least {}",
+                    committed_size, upload.upload_id, upload.committed_size
+                )))
+            }
+            WriteStatus::Incomplete { committed_size } => {
+                let skip = (committed_size - upload.committed_size) as usize;
+                self.upload_stream(upload, skip, bytes).await
+            }
+        }
+    }
+
+    /// Upload a stream in chunks, after first skipping `skip` bytes that were already committed
+    async fn upload_stream<S>(
+        &self,
+        upload: &mut ResumableUpload,
+        mut skip: usize,
+        bytes: S,
+    ) -> Result<Object>
+    where
+        S: Stream<Item = Bytes>,
+    {
+        futures::pin_mut!(bytes);
+
+        let mut buffer = BytesMut::new();
+
+        loop {
+            let next = bytes.next().await;
+
+            if let Some(mut next) = next.clone() {
+                if skip > 0 {
+                    let committed = next.split_to(skip.min(next.len()));
+                    upload.commit(&committed);
+                    skip -= committed.len();
+                }
+
+                buffer.extend_from_slice(&next);
+            }
+
+            // hold back at least one byte until the stream ends so the final chunk carries data
+            while buffer.len() > DEFAULT_CHUNK_SIZE {
+                let chunk = buffer.split_to(DEFAULT_CHUNK_SIZE).freeze();
+                self.write_resumable_upload(upload, chunk).await?;
+            }
+
+            if next.is_none() {
+                if skip > 0 {
+                    return Err(other_error(format!(
+                        "Stream ended before the {} bytes committed to upload {}",
+                        upload.committed_size as usize + skip,
+                        upload.upload_id
+                    )));
+                }
+
+                return self
+                    .finish_resumable_upload(upload, buffer.split().freeze())
+                    .await;
+            }
+        }
+    }
+}
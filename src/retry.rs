@@ -0,0 +1,210 @@
+use crate::error::other_error;
+use crate::{GoogleResponse, Result};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+/// Controls how requests that fail with a transient error are retried
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    deadline: Option<Duration>,
+    always_retry: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Exponential backoff as recommended by https://cloud.google.com/storage/docs/exponential-backoff
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 6,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(32),
+            multiplier: 2.0,
+            jitter: 0.5,
+            deadline: Some(Duration::from_secs(120)),
+            always_retry: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The maximum number of times a request is sent, including the first attempt
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// The upper bound on the delay between two attempts
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// The factor the delay grows by after each retry
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// The fraction of each delay that is randomized, between 0 and 1
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// No retry is started once this much time has passed since the first attempt, and an
+    /// attempt that can be retried fails if no response arrives before it is reached. Reading the
+    /// body of a response is not bounded, so long downloads are not cut off.
+    pub fn deadline(mut self, deadline: impl Into<Option<Duration>>) -> Self {
+        self.deadline = deadline.into();
+        self
+    }
+
+    /// Retry requests that are not idempotent, by default only requests that can be safely
+    /// sent more than once are retried
+    pub fn always_retry(mut self, always_retry: bool) -> Self {
+        self.always_retry = always_retry;
+        self
+    }
+
+    /// The delay after the given (1-based) failed attempt
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+
+        // a random number in [0, 1), without jitter if no random bytes are available
+        let mut bytes = [0u8; 8];
+        let random = match SystemRandom::new().fill(&mut bytes) {
+            Ok(()) => (u64::from_be_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64,
+            Err(_) => 0.0,
+        };
+
+        Duration::from_secs_f64(backoff * (1.0 - self.jitter * random))
+    }
+
+    /// The delay before retrying an operation started at `start` after its given (1-based)
+    /// attempt failed with `error`, or `None` when it should not be retried
+    pub(crate) fn retry_after(
+        &self,
+        error: &crate::Error,
+        attempt: u32,
+        start: Instant,
+    ) -> Option<Duration> {
+        if !is_retryable(error) || attempt >= self.max_attempts {
+            return None;
+        }
+
+        let backoff = self.backoff(attempt);
+
+        match self.deadline {
+            Some(deadline) if start.elapsed() + backoff > deadline => None,
+            _ => Some(backoff),
+        }
+    }
+
+    /// Send an attempt, failing if the response has not arrived by the deadline. Only the wait
+    /// for the response is bounded, a request timeout would also cut off reading the body.
+    async fn send_before_deadline(
+        &self,
+        request: RequestBuilder,
+        start: Instant,
+    ) -> Result<Response> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return send(request).await,
+        };
+
+        let remaining = deadline.checked_sub(start.elapsed()).unwrap_or_default();
+
+        tokio::time::timeout(remaining, send(request))
+            .await
+            .unwrap_or_else(|_| {
+                Err(other_error(format!(
+                    "No response within the {:?} retry deadline",
+                    deadline
+                )))
+            })
+    }
+
+    pub(crate) async fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<Response> {
+        if !idempotent && !self.always_retry {
+            return send(request).await;
+        }
+
+        let start = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            // streaming bodies cannot be cloned and are only sent once
+            let retry = match request.try_clone() {
+                Some(retry) => retry,
+                None => return send(request).await,
+            };
+
+            match self.send_before_deadline(retry, start).await {
+                Err(err) => match self.retry_after(&err, attempt, start) {
+                    Some(backoff) => {
+                        tracing::debug!(error = %err, attempt, ?backoff, "retrying request");
+
+                        tokio::time::delay_for(backoff).await;
+                        attempt += 1;
+                    }
+                    None => return Err(err),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+pub(crate) async fn send(request: RequestBuilder) -> Result<Response> {
+    request
+        .send()
+        .instrument(tracing::trace_span!("sending"))
+        .await?
+        .into_google_response()
+        .instrument(tracing::trace_span!("error test"))
+        .await
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// Transient errors: timeouts, rate limits, server errors and failures to reach the service
+pub(crate) fn is_retryable(error: &crate::Error) -> bool {
+    match error {
+        crate::Error::Google { source, .. } => StatusCode::from_u16(source.code())
+            .map(is_retryable_status)
+            .unwrap_or_default(),
+        crate::Error::Reqwest { source, .. } => {
+            source.is_connect()
+                || source.is_timeout()
+                || source.is_request()
+                || source.status().map(is_retryable_status).unwrap_or_default()
+        }
+        _ => false,
+    }
+}
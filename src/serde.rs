@@ -93,6 +93,37 @@ pub(crate) mod optional_timestamp {
     }
 }
 
+pub(crate) mod optional_timestamp_millis {
+    use prost_types::Timestamp;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::convert::TryInto;
+
+    pub fn serialize<S>(value: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value
+            .as_ref()
+            .map(|value| (value.seconds * 1000 + i64::from(value.nanos) / 1_000_000).to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = match Option::<String>::deserialize(deserializer)? {
+            Some(value) => value.parse::<i64>().map_err(::serde::de::Error::custom)?,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Timestamp {
+            seconds: value.div_euclid(1000),
+            nanos: (value.rem_euclid(1000) * 1_000_000).try_into().unwrap(),
+        }))
+    }
+}
+
 pub(crate) mod optional_field_mask {
     use prost_types::FieldMask;
     use serde::{Deserializer, Serializer};
@@ -0,0 +1,40 @@
+use crate::google::storage::v1::{GetProjectServiceAccountRequest, ServiceAccount};
+use crate::query::Query;
+use crate::request::Request;
+use crate::urls::Urls;
+use crate::{Client, Result};
+use reqwest::Method;
+use std::fmt::Debug;
+use url::Url;
+
+impl Query for GetProjectServiceAccountRequest {
+    fn request_query(&mut self) -> Vec<(&'static str, String)> {
+        self.common_request_params.request_query()
+    }
+}
+
+impl Request for GetProjectServiceAccountRequest {
+    const REQUEST_METHOD: Method = Method::GET;
+
+    type Response = ServiceAccount;
+
+    fn request_path(&self, base_url: Url) -> Result<Url> {
+        base_url
+            .join("projects/")?
+            .join_segment(&self.project_id)?
+            .join_segment("serviceAccount")
+    }
+}
+
+impl Client {
+    #[doc = " Retrieves the name of a project's Google Cloud Storage service account."]
+    #[tracing::instrument]
+    pub async fn get_service_account(
+        &self,
+        request: impl Into<GetProjectServiceAccountRequest> + Debug,
+    ) -> Result<ServiceAccount> {
+        let request = request.into();
+
+        self.invoke(request).await
+    }
+}
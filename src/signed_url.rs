@@ -0,0 +1,318 @@
+use crate::encode;
+use crate::error::other_error;
+use crate::signer::Signer;
+use crate::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::{HeaderMap, HeaderName, AUTHORIZATION};
+use reqwest::Method;
+use ring::digest::{digest, SHA256};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+
+/// The longest expiration allowed for a V4 signature
+pub(crate) const MAX_EXPIRATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// The endpoint, defaulting to `https://storage.googleapis.com`, and its `host[:port]`
+pub(crate) fn endpoint_host(endpoint: Option<&Url>) -> Result<(Url, String)> {
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => Url::parse(DEFAULT_ENDPOINT)?,
+    };
+
+    let host = match (endpoint.host_str(), endpoint.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => {
+            return Err(crate::Error::InvalidRequestUrl {
+                url: endpoint,
+                #[cfg(feature = "backtrace")]
+                backtrace: std::backtrace::Backtrace::capture(),
+            })
+        }
+    };
+
+    Ok((endpoint, host))
+}
+
+pub(crate) fn hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The current time, truncated to seconds
+pub(crate) fn now() -> DateTime<Utc> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Utc.timestamp_opt(now.as_secs() as i64, 0).unwrap()
+}
+
+/// How the bucket is addressed in a signed URL
+#[derive(Clone, Debug, PartialEq)]
+pub enum UrlStyle {
+    /// `https://storage.googleapis.com/bucket/object`
+    PathStyle,
+    /// `https://bucket.storage.googleapis.com/object`
+    VirtualHostedStyle,
+    /// `https://example.com/object`, where the endpoint is a custom domain that is mapped to
+    /// the bucket
+    BucketBoundHostname,
+}
+
+impl Default for UrlStyle {
+    fn default() -> Self {
+        UrlStyle::PathStyle
+    }
+}
+
+/// A V4 signed URL, see https://cloud.google.com/storage/docs/access-control/signing-urls-manually
+#[derive(Clone, Debug)]
+pub struct SignedUrl {
+    method: Method,
+    bucket: String,
+    object: String,
+    expires_in: Duration,
+    headers: BTreeMap<String, String>,
+    query: Vec<(String, String)>,
+    style: UrlStyle,
+    endpoint: Option<Url>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl SignedUrl {
+    /// A URL for `method` on an object, or on the bucket itself when `object` is empty
+    pub fn new(method: Method, bucket: impl Into<String>, object: impl Into<String>) -> Self {
+        SignedUrl {
+            method,
+            bucket: bucket.into(),
+            object: object.into(),
+            expires_in: Duration::from_secs(60 * 60),
+            headers: Default::default(),
+            query: Default::default(),
+            style: Default::default(),
+            endpoint: None,
+            timestamp: None,
+        }
+    }
+
+    /// How long the URL is valid for, at most 7 days. Defaults to one hour.
+    pub fn expires_in(mut self, expires_in: Duration) -> Self {
+        self.expires_in = expires_in;
+        self
+    }
+
+    /// A header that must be sent with the same value when the URL is used
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        // values are trimmed and runs of whitespace are folded into a single space
+        let value = value
+            .as_ref()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.headers
+            .insert(name.as_ref().to_ascii_lowercase(), value);
+        self
+    }
+
+    /// An additional query parameter, such as `response-content-disposition`
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn style(mut self, style: UrlStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// The scheme and host the URL points at, defaults to `https://storage.googleapis.com`
+    pub fn endpoint(mut self, endpoint: impl Into<Url>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// The time the signature becomes valid, defaults to now
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    fn host_and_path(&self) -> Result<(Url, String, String)> {
+        let (endpoint, host) = endpoint_host(self.endpoint.as_ref())?;
+
+        let object = encode::signing_path(&self.object);
+
+        Ok(match self.style {
+            UrlStyle::PathStyle if object.is_empty() => (
+                endpoint,
+                host,
+                format!("/{}", encode::signing(&self.bucket)),
+            ),
+            UrlStyle::PathStyle => (
+                endpoint,
+                host,
+                format!("/{}/{}", encode::signing(&self.bucket), object),
+            ),
+            UrlStyle::VirtualHostedStyle => (
+                endpoint,
+                format!("{}.{}", self.bucket, host),
+                format!("/{}", object),
+            ),
+            UrlStyle::BucketBoundHostname => (endpoint, host, format!("/{}", object)),
+        })
+    }
+
+    /// The canonical request and signature, with the credentials either in the query string
+    /// or in headers
+    async fn signature<S: Signer + ?Sized>(&self, signer: &S, in_query: bool) -> Result<Signature> {
+        if self.expires_in > MAX_EXPIRATION {
+            return Err(other_error(
+                "Signed URLs expire after at most 7 days".into(),
+            ));
+        }
+
+        let timestamp = self.timestamp.unwrap_or_else(now);
+
+        let date = timestamp.format("%Y%m%d").to_string();
+        let datetime = timestamp.format("%Y%m%dT%H%M%SZ").to_string();
+        let credential_scope = format!("{}/auto/storage/goog4_request", date);
+        let credential = format!("{}/{}", signer.client_email(), credential_scope);
+
+        let (endpoint, host, path) = self.host_and_path()?;
+
+        let mut headers = self.headers.clone();
+        headers.insert("host".to_string(), host.clone());
+        if !in_query {
+            headers.insert("x-goog-date".to_string(), datetime.clone());
+            headers
+                .entry("x-goog-content-sha256".to_string())
+                .or_insert_with(|| UNSIGNED_PAYLOAD.to_string());
+        }
+
+        let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+
+        let mut query = self.query.clone();
+        if in_query {
+            query.extend(vec![
+                (
+                    "X-Goog-Algorithm".to_string(),
+                    signer.algorithm().to_string(),
+                ),
+                ("X-Goog-Credential".to_string(), credential.clone()),
+                ("X-Goog-Date".to_string(), datetime.clone()),
+                (
+                    "X-Goog-Expires".to_string(),
+                    self.expires_in.as_secs().to_string(),
+                ),
+                ("X-Goog-SignedHeaders".to_string(), signed_headers.clone()),
+            ]);
+        }
+
+        let mut query = query
+            .iter()
+            .map(|(k, v)| (encode::signing(k), encode::signing(v)))
+            .collect::<Vec<_>>();
+        query.sort();
+
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_headers = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect::<String>();
+
+        let payload = headers
+            .get("x-goog-content-sha256")
+            .map(String::as_str)
+            .unwrap_or(UNSIGNED_PAYLOAD);
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.method, path, canonical_query, canonical_headers, signed_headers, payload
+        );
+
+        tracing::trace!(%canonical_request);
+
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            signer.algorithm(),
+            datetime,
+            credential_scope,
+            hex(digest(&SHA256, canonical_request.as_bytes()))
+        );
+
+        let signature = hex(signer.sign(&date, string_to_sign.as_bytes()).await?);
+
+        let mut url = format!("{}://{}{}", endpoint.scheme(), host, path);
+        if !canonical_query.is_empty() {
+            url.push('?');
+            url.push_str(&canonical_query);
+        }
+
+        Ok(Signature {
+            url,
+            headers,
+            credential,
+            signed_headers,
+            signature,
+        })
+    }
+
+    /// Sign the URL, the credentials and signature are added to the query string
+    pub async fn sign<S: Signer + ?Sized>(&self, signer: &S) -> Result<Url> {
+        let signature = self.signature(signer, true).await?;
+
+        Ok(Url::parse(&format!(
+            "{}&X-Goog-Signature={}",
+            signature.url, signature.signature
+        ))?)
+    }
+
+    /// Sign a request, returning the URL and the headers to send with it, including the
+    /// `Authorization` header. The expiration does not apply to signed requests.
+    pub async fn sign_request<S: Signer + ?Sized>(&self, signer: &S) -> Result<(Url, HeaderMap)> {
+        let signature = self.signature(signer, false).await?;
+
+        let mut headers = HeaderMap::with_capacity(signature.headers.len());
+        for (name, value) in signature.headers.iter().filter(|(name, _)| *name != "host") {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| other_error(format!("Invalid signed header name {}: {}", name, e)))?;
+            headers.insert(name, value.parse()?);
+        }
+
+        headers.insert(
+            AUTHORIZATION,
+            format!(
+                "{} Credential={}, SignedHeaders={}, Signature={}",
+                signer.algorithm(),
+                signature.credential,
+                signature.signed_headers,
+                signature.signature
+            )
+            .parse()?,
+        );
+
+        Ok((Url::parse(&signature.url)?, headers))
+    }
+}
+
+struct Signature {
+    url: String,
+    headers: BTreeMap<String, String>,
+    credential: String,
+    signed_headers: String,
+    signature: String,
+}
@@ -0,0 +1,273 @@
+use crate::credentials::{
+    rsa_key_pair, service_account_name, ServiceAccountKey, CLOUD_PLATFORM_SCOPE,
+    IAM_CREDENTIALS_ENDPOINT,
+};
+use crate::error::other_error;
+use crate::google::storage::v1::CreateHmacKeyResponse;
+use crate::headers::Headers;
+use crate::{GoogleResponse, Result};
+use async_trait::async_trait;
+use ring::hmac;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Formatter};
+use std::path::Path;
+use url::Url;
+
+/// Produces V4 signatures for signed URLs, signed requests and POST policies
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// The `X-Goog-Algorithm`, such as `GOOG4-RSA-SHA256`
+    fn algorithm(&self) -> &'static str;
+
+    /// The email address of the service account, or the access ID of an HMAC key
+    fn client_email(&self) -> &str;
+
+    /// Sign the string-to-sign, `date` is the `YYYYMMDD` of the credential scope
+    async fn sign(&self, date: &str, message: &[u8]) -> Result<Vec<u8>>;
+}
+
+#[async_trait]
+impl<S: Signer + ?Sized> Signer for Box<S> {
+    fn algorithm(&self) -> &'static str {
+        (**self).algorithm()
+    }
+
+    fn client_email(&self) -> &str {
+        (**self).client_email()
+    }
+
+    async fn sign(&self, date: &str, message: &[u8]) -> Result<Vec<u8>> {
+        (**self).sign(date, message).await
+    }
+}
+
+enum SigningKey {
+    Rsa(Box<RsaKeyPair>),
+    Hmac(String),
+}
+
+/// Signs V4 URLs and requests with a service account private key or an HMAC key
+pub struct UrlSigner {
+    client_email: String,
+    key: SigningKey,
+}
+
+impl Debug for UrlSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("client_email", &self.client_email)
+            .field("algorithm", &self.signing_algorithm())
+            .finish()
+    }
+}
+
+impl UrlSigner {
+    /// Parse the contents of a service account JSON key file
+    pub fn from_json(json: impl AsRef<[u8]>) -> Result<Self> {
+        let key: ServiceAccountKey = serde_json::from_slice(json.as_ref())?;
+
+        Ok(UrlSigner {
+            key: SigningKey::Rsa(Box::new(rsa_key_pair(&key.private_key)?)),
+            client_email: key.client_email,
+        })
+    }
+
+    /// Read a service account JSON key file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(std::fs::read(path)?)
+    }
+
+    /// Sign with an HMAC key, identified by its access ID
+    pub fn hmac(access_id: impl Into<String>, secret: impl Into<String>) -> Self {
+        UrlSigner {
+            client_email: access_id.into(),
+            key: SigningKey::Hmac(secret.into()),
+        }
+    }
+
+    fn signing_algorithm(&self) -> &'static str {
+        match self.key {
+            SigningKey::Rsa(_) => "GOOG4-RSA-SHA256",
+            SigningKey::Hmac(_) => "GOOG4-HMAC-SHA256",
+        }
+    }
+
+    fn sign_message(&self, date: &str, message: &[u8]) -> Result<Vec<u8>> {
+        match &self.key {
+            SigningKey::Rsa(key_pair) => {
+                let mut signature = vec![0; key_pair.public_modulus_len()];
+                key_pair
+                    .sign(
+                        &RSA_PKCS1_SHA256,
+                        &SystemRandom::new(),
+                        message,
+                        &mut signature,
+                    )
+                    .map_err(|_| other_error("Failed to sign the URL".into()))?;
+
+                Ok(signature)
+            }
+            SigningKey::Hmac(secret) => {
+                // the signing key is derived from the secret and each part of the credential scope
+                let key = format!("GOOG4{}", secret);
+                let key = [date, "auto", "storage", "goog4_request"].iter().fold(
+                    key.into_bytes(),
+                    |key, part| {
+                        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes())
+                            .as_ref()
+                            .to_vec()
+                    },
+                );
+
+                Ok(
+                    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), message)
+                        .as_ref()
+                        .to_vec(),
+                )
+            }
+        }
+    }
+}
+
+/// Sign with the HMAC key that was just created
+impl TryFrom<CreateHmacKeyResponse> for UrlSigner {
+    type Error = crate::Error;
+
+    fn try_from(value: CreateHmacKeyResponse) -> Result<Self> {
+        let metadata = value
+            .metadata
+            .ok_or_else(|| other_error("Expected metadata field".into()))?;
+
+        Ok(UrlSigner::hmac(metadata.access_id, value.secret))
+    }
+}
+
+#[async_trait]
+impl Signer for UrlSigner {
+    fn algorithm(&self) -> &'static str {
+        self.signing_algorithm()
+    }
+
+    fn client_email(&self) -> &str {
+        &self.client_email
+    }
+
+    async fn sign(&self, date: &str, message: &[u8]) -> Result<Vec<u8>> {
+        self.sign_message(date, message)
+    }
+}
+
+#[derive(Serialize)]
+struct SignBlobRequest<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    delegates: &'a [String],
+    payload: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignBlobResponse {
+    signed_blob: String,
+}
+
+/// Signs with the IAM Credentials `signBlob` API, for when there is no private key such as on
+/// GKE with Workload Identity. The source credentials need `roles/iam.serviceAccountTokenCreator`
+/// on the service account.
+pub struct IamSigner {
+    source: Box<dyn Headers>,
+    service_account: String,
+    delegates: Vec<String>,
+    endpoint: Url,
+    client: reqwest::Client,
+}
+
+impl Debug for IamSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IamSigner")
+            .field("service_account", &self.service_account)
+            .field("delegates", &self.delegates)
+            .field("endpoint", &self.endpoint.to_string())
+            .finish()
+    }
+}
+
+impl IamSigner {
+    /// Sign as `service_account`, authenticating the `signBlob` calls with `source`
+    pub fn new(source: impl Headers + 'static, service_account: impl Into<String>) -> Result<Self> {
+        Ok(IamSigner {
+            source: Box::new(source),
+            service_account: service_account.into(),
+            delegates: vec![],
+            endpoint: Url::parse(IAM_CREDENTIALS_ENDPOINT)?,
+            client: Default::default(),
+        })
+    }
+
+    /// The service accounts in a delegation chain, each must be granted
+    /// `roles/iam.serviceAccountTokenCreator` on the next, with the last one on the signer
+    pub fn delegates<T: Into<String>>(mut self, delegates: impl IntoIterator<Item = T>) -> Self {
+        self.delegates = delegates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Override the IAM Credentials API endpoint, defaults to `https://iamcredentials.googleapis.com/v1/`
+    pub fn endpoint(mut self, endpoint: impl Into<Url>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    pub fn client(mut self, client: impl Into<reqwest::Client>) -> Self {
+        self.client = client.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Signer for IamSigner {
+    fn algorithm(&self) -> &'static str {
+        "GOOG4-RSA-SHA256"
+    }
+
+    fn client_email(&self) -> &str {
+        &self.service_account
+    }
+
+    #[tracing::instrument(skip(self, message))]
+    async fn sign(&self, date: &str, message: &[u8]) -> Result<Vec<u8>> {
+        let url = self.endpoint.join(&format!(
+            "{}:signBlob",
+            service_account_name(&self.service_account)
+        ))?;
+
+        let delegates = self
+            .delegates
+            .iter()
+            .map(|delegate| service_account_name(delegate))
+            .collect::<Vec<_>>();
+
+        let response: SignBlobResponse = self
+            .client
+            .post(url)
+            .headers(self.source.headers(CLOUD_PLATFORM_SCOPE).await?)
+            .json(&SignBlobRequest {
+                delegates: &delegates,
+                payload: base64::encode(message),
+            })
+            .send()
+            .await?
+            .into_google_response()
+            .await?
+            .json()
+            .await?;
+
+        base64::decode(&response.signed_blob).map_err(|e| {
+            other_error(format!(
+                "Invalid signed blob in the signBlob response: {}",
+                e
+            ))
+        })
+    }
+}
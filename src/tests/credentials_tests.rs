@@ -0,0 +1,117 @@
+use crate::credentials::detect_credentials;
+use httptest::{all_of, cycle, matchers::*, responders::*, Expectation, Server};
+use serde_json::json;
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+/// Look up variables in `vars` instead of the process environment
+fn env(vars: &[(&str, String)]) -> impl Fn(&str) -> Option<OsString> {
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), OsString::from(value)))
+        .collect::<HashMap<_, _>>();
+
+    move |name| vars.get(name).cloned()
+}
+
+#[tokio::test]
+async fn application_default_credentials_env() -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/token"),
+            request::body(url_decoded(contains(("grant_type", "refresh_token")))),
+            request::body(url_decoded(contains(("client_id", "client-id")))),
+            request::body(url_decoded(contains(("refresh_token", "refresh-token")))),
+        ])
+        .respond_with(json_encoded(json!({
+            "access_token": "ya29.user",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))),
+    );
+
+    let path = std::env::temp_dir().join(format!("adc-{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        serde_json::to_vec(&json!({
+            "type": "authorized_user",
+            "client_id": "client-id",
+            "client_secret": "client-secret",
+            "refresh_token": "refresh-token",
+            "token_uri": server.url_str("/token")
+        }))?,
+    )?;
+
+    let credentials = detect_credentials(env(&[(
+        "GOOGLE_APPLICATION_CREDENTIALS",
+        path.display().to_string(),
+    )]));
+    std::fs::remove_file(&path)?;
+
+    let headers = credentials?
+        .headers("https://www.googleapis.com/auth/devstorage.read_only")
+        .await?;
+
+    assert_eq!(headers["authorization"], "Bearer ya29.user");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn application_default_credentials_metadata_server() -> Result<(), Box<dyn std::error::Error>>
+{
+    let server = Server::run();
+
+    // the first probe fails, which is not remembered
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/"),
+            request::headers(contains(("metadata-flavor", "Google"))),
+        ])
+        .times(2)
+        .respond_with(cycle![
+            status_code(503),
+            status_code(200).insert_header("Metadata-Flavor", "Google"),
+        ]),
+    );
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+        ))
+        .times(2)
+        .respond_with(json_encoded(json!({
+            "access_token": "ya29.metadata",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))),
+    );
+
+    let credentials = detect_credentials(env(&[("GCE_METADATA_HOST", server.addr().to_string())]))?;
+
+    assert!(credentials.headers("a").await.is_err());
+
+    let headers = credentials.headers("a").await?;
+    assert_eq!(headers["authorization"], "Bearer ya29.metadata");
+
+    let headers = credentials.headers("b").await?;
+    assert_eq!(headers["authorization"], "Bearer ya29.metadata");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn application_default_credentials_not_found() -> Result<(), Box<dyn std::error::Error>> {
+    // nothing listens on the discard port
+    let credentials = detect_credentials(env(&[("GCE_METADATA_HOST", "127.0.0.1:9".into())]))?;
+
+    let err = credentials.headers("a").await.unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("No application default credentials found"));
+
+    Ok(())
+}
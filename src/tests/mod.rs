@@ -1,2 +1,4 @@
 pub mod bucket_tests;
+pub mod credentials_tests;
 pub mod object_tests;
+pub mod query_tests;
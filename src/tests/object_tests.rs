@@ -1,7 +1,14 @@
+use crate::google::storage::v1::common_enums::{PredefinedObjectAcl, Projection};
 use crate::google::storage::v1::{CopyObjectRequest, ListObjectsRequest, ListObjectsResponse};
 use crate::request::Request;
-use crate::storage::v1::{GetObjectRequest, RewriteObjectRequest};
+use crate::storage::v1::{
+    CommonObjectRequestParams, DeleteObjectRequest, GetObjectRequest, Object,
+    PatchObjectAccessControlRequest, RewriteObjectRequest, UpdateObjectRequest,
+};
+use crate::Client;
+use httptest::{all_of, matchers::*, responders::*, Expectation, Server};
 use prost_types::Timestamp;
+use url::Url;
 
 #[test]
 fn list_objects_url() {
@@ -44,6 +51,37 @@ fn get_object_url_q() {
     );
 }
 
+#[test]
+fn get_object_url_special_characters() {
+    let base_url: Url = "https://storage.googleapis.com/storage/v1/"
+        .parse()
+        .unwrap();
+
+    for (object, encoded) in &[
+        ("100%", "100%25"),
+        ("a b", "a%20b"),
+        ("a+b", "a%2Bb"),
+        ("%2F", "%252F"),
+        ("caf\u{e9}/\u{1f600}", "caf%C3%A9/%F0%9F%98%80"),
+    ] {
+        let request = GetObjectRequest {
+            bucket: "bucket".to_string(),
+            object: object.to_string(),
+            ..Default::default()
+        };
+
+        let url = request.request_path(base_url.clone()).unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            format!(
+                "https://storage.googleapis.com/storage/v1/b/bucket/o/{}",
+                encoded
+            )
+        );
+    }
+}
+
 #[test]
 fn copy_object_url() {
     let request = CopyObjectRequest {
@@ -110,6 +148,29 @@ fn get_object_url() {
     );
 }
 
+#[test]
+fn patch_object_access_control_url() {
+    let request = PatchObjectAccessControlRequest {
+        bucket: "bucket".to_string(),
+        object: "foo/bar".to_string(),
+        entity: "allUsers".to_string(),
+        ..Default::default()
+    };
+
+    let url = request
+        .request_path(
+            "https://storage.googleapis.com/storage/v1/"
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        url.as_str(),
+        "https://storage.googleapis.com/storage/v1/b/bucket/o/foo/bar/acl/allUsers"
+    );
+}
+
 #[test]
 fn valid_objects_list() {
     let res = serde_json::from_str::<ListObjectsResponse>(include_str!("valid_objects_list.json"))
@@ -223,3 +284,136 @@ fn valid_crc32c() {
         Some(crc32c::crc32c(include_bytes!("BingSiteAuth.xml")))
     );
 }
+
+#[tokio::test]
+async fn get_object_query() -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/b/bucket/o/object"),
+            request::query(eq("x-goog-encryption-algorithm=AES256\
+                &x-goog-encryption-key=a2V5\
+                &x-goog-encryption-key-sha256=c2hh\
+                &generation=5\
+                &ifGenerationMatch=5\
+                &ifMetagenerationNotMatch=2\
+                &projection=full")),
+        ])
+        .respond_with(status_code(200).body(r#"{"bucket": "bucket", "name": "object"}"#)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .get_object(GetObjectRequest {
+            bucket: "bucket".to_string(),
+            object: "object".to_string(),
+            generation: 5,
+            if_generation_match: Some(5),
+            if_metageneration_not_match: Some(2),
+            projection: Projection::Full as i32,
+            common_object_request_params: Some(CommonObjectRequestParams {
+                encryption_algorithm: "AES256".to_string(),
+                encryption_key: "key".to_string(),
+                encryption_key_sha256: "sha".to_string(),
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(object.name, "object");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_object_query() -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/storage/v1/b/bucket/o/object"),
+            request::query(eq("generation=7\
+                &ifGenerationNotMatch=6\
+                &ifMetagenerationMatch=3\
+                &predefinedAcl=publicRead\
+                &projection=noAcl")),
+            request::body(json_decoded(eq(serde_json::json!({
+                "contentType": "text/plain",
+            })))),
+        ])
+        .respond_with(
+            status_code(200)
+                .body(r#"{"bucket": "bucket", "name": "object", "contentType": "text/plain"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .update_object(UpdateObjectRequest {
+            bucket: "bucket".to_string(),
+            object: "object".to_string(),
+            generation: 7,
+            if_generation_not_match: Some(6),
+            if_metageneration_match: Some(3),
+            predefined_acl: PredefinedObjectAcl::ObjectAclPublicRead as i32,
+            projection: Projection::NoAcl as i32,
+            metadata: Some(Object {
+                content_type: "text/plain".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(object.content_type, "text/plain");
+
+    Ok(())
+}
+
+#[test]
+fn object_request_idempotency() {
+    assert!(GetObjectRequest::default().is_idempotent());
+
+    assert!(!DeleteObjectRequest::default().is_idempotent());
+    assert!(DeleteObjectRequest {
+        if_generation_match: Some(1),
+        ..Default::default()
+    }
+    .is_idempotent());
+    assert!(DeleteObjectRequest {
+        generation: 3,
+        ..Default::default()
+    }
+    .is_idempotent());
+
+    assert!(!RewriteObjectRequest::default().is_idempotent());
+    assert!(RewriteObjectRequest {
+        if_generation_match: Some(0),
+        ..Default::default()
+    }
+    .is_idempotent());
+
+    assert!(!UpdateObjectRequest::default().is_idempotent());
+    assert!(UpdateObjectRequest {
+        if_metageneration_match: Some(2),
+        ..Default::default()
+    }
+    .is_idempotent());
+}
+
+#[test]
+fn multipart_boundary_is_unique() {
+    let first = crate::object::multipart_boundary().unwrap();
+    let second = crate::object::multipart_boundary().unwrap();
+
+    assert_eq!(first.len(), 32);
+    assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(first, second);
+}
@@ -0,0 +1,82 @@
+use crate::query::{PushIf, Query};
+use crate::storage::v1::common_enums::{PredefinedBucketAcl, PredefinedObjectAcl, Projection};
+use crate::storage::v1::{
+    CopyObjectRequest, GetObjectRequest, InsertBucketRequest, PatchObjectRequest,
+};
+
+#[test]
+fn push_if_skips_default_values() {
+    let mut query = vec![];
+
+    query.push_if("generation", &mut 0i64);
+    query.push_if("generation", &mut 5i64);
+
+    assert_eq!(query, vec![("generation", "5".to_string())]);
+}
+
+#[test]
+fn push_if_opt_sends_explicit_default_values() {
+    let mut query = vec![];
+
+    query.push_if_opt("ifGenerationMatch", &mut Some(0i64));
+    query.push_if_opt("ifMetagenerationMatch", &mut None::<i64>);
+    query.push_if_opt("flag", &mut Some(false));
+
+    assert_eq!(
+        query,
+        vec![
+            ("ifGenerationMatch", "0".to_string()),
+            ("flag", "false".to_string())
+        ]
+    );
+}
+
+// `ifGenerationMatch=0` makes a request succeed only when the object does not exist yet
+#[test]
+fn get_object_if_generation_match_zero() {
+    let mut request = GetObjectRequest {
+        bucket: "bucket".to_string(),
+        object: "object".to_string(),
+        if_generation_match: Some(0),
+        ..Default::default()
+    };
+
+    assert!(request
+        .request_query()
+        .contains(&("ifGenerationMatch", "0".to_string())));
+}
+
+// enum values are sent under the key of the field they are set on
+#[test]
+fn predefined_acls_use_their_field_key() {
+    let mut request = InsertBucketRequest {
+        predefined_acl: PredefinedBucketAcl::BucketAclPrivate as i32,
+        predefined_default_object_acl: PredefinedObjectAcl::ObjectAclPublicRead as i32,
+        projection: Projection::Full as i32,
+        ..Default::default()
+    };
+
+    let query = request.request_query();
+    assert!(query.contains(&("predefinedAcl", "private".to_string())));
+    assert!(query.contains(&("predefinedDefaultObjectAcl", "publicRead".to_string())));
+    assert!(query.contains(&("projection", "full".to_string())));
+
+    let mut request = PatchObjectRequest {
+        predefined_acl: PredefinedObjectAcl::ObjectAclProjectPrivate as i32,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        request.request_query(),
+        vec![("predefinedAcl", "projectPrivate".to_string())]
+    );
+
+    let mut request = CopyObjectRequest {
+        destination_predefined_acl: PredefinedObjectAcl::ObjectAclBucketOwnerRead as i32,
+        ..Default::default()
+    };
+
+    assert!(request
+        .request_query()
+        .contains(&("destinationPredefinedAcl", "bucketOwnerRead".to_string())));
+}
@@ -1,8 +1,15 @@
 mod util;
 
-use google_cloud_storage::storage::v1::{Bucket, InsertBucketRequest};
+use google_cloud_storage::storage::v1::bucket::Versioning;
+use google_cloud_storage::storage::v1::common_enums::PredefinedBucketAcl;
+use google_cloud_storage::storage::v1::{
+    Bucket, BucketAccessControl, InsertBucketRequest, LockRetentionPolicyRequest,
+    PatchBucketAccessControlRequest, PatchBucketRequest,
+};
 use google_cloud_storage::Client;
 use httptest::{matchers::*, responders::*, Expectation, Server};
+use prost_types::FieldMask;
+use serde_json::json;
 use url::Url;
 
 #[tokio::test]
@@ -34,3 +41,133 @@ async fn insert_bucket() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn patch_bucket() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PATCH", "/storage/v1/b/new-bucket"),
+            request::query(url_decoded(contains(("ifMetagenerationMatch", "1")))),
+            request::query(url_decoded(contains(("predefinedAcl", "private")))),
+            request::body(json_decoded(eq(json!({
+                "labels": {"env": "prod"},
+                "versioning": {"enabled": true},
+            })))),
+        ])
+        .respond_with(status_code(200).body(include_str!("../src/tests/valid_bucket.json"))),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let bucket = client
+        .patch_bucket(PatchBucketRequest {
+            bucket: "new-bucket".to_string(),
+            if_metageneration_match: Some(1),
+            predefined_acl: PredefinedBucketAcl::BucketAclPrivate as i32,
+            metadata: Some(Bucket {
+                name: "ignored".to_string(),
+                labels: vec![("env".to_string(), "prod".to_string())]
+                    .into_iter()
+                    .collect(),
+                versioning: Some(Versioning { enabled: true }),
+                ..Default::default()
+            }),
+            update_mask: Some(FieldMask {
+                paths: vec!["labels".to_string(), "versioning.enabled".to_string()],
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(bucket.name, "new-bucket");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn patch_bucket_access_control() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PATCH", "/storage/v1/b/bucket/acl/allUsers"),
+            request::body(json_decoded(eq(json!({"role": "READER"})))),
+        ])
+        .respond_with(
+            status_code(200)
+                .body(r#"{"bucket": "bucket", "entity": "allUsers", "role": "READER"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let acl = client
+        .patch_bucket_access_control(PatchBucketAccessControlRequest {
+            bucket: "bucket".to_string(),
+            entity: "allUsers".to_string(),
+            bucket_access_control: Some(BucketAccessControl {
+                role: "READER".to_string(),
+                entity: "ignored".to_string(),
+                ..Default::default()
+            }),
+            update_mask: Some(FieldMask {
+                paths: vec!["role".to_string()],
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(acl.role, "READER");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lock_bucket_retention_policy() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/storage/v1/b/new-bucket/lockRetentionPolicy"),
+            request::query(url_decoded(contains(("ifMetagenerationMatch", "1")))),
+        ])
+        .respond_with(status_code(200).body(include_str!("../src/tests/valid_bucket.json"))),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let bucket = Bucket {
+        name: "new-bucket".to_string(),
+        metageneration: 1,
+        ..Default::default()
+    };
+
+    let bucket = client.lock_bucket_retention_policy(bucket).await?;
+
+    assert_eq!(bucket.name, "new-bucket");
+
+    let unguarded = client
+        .lock_bucket_retention_policy(LockRetentionPolicyRequest {
+            bucket: "new-bucket".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(unguarded.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+mod util;
+
+use google_cloud_storage::storage::v1::{
+    Channel, ListChannelsRequest, StopChannelRequest, WatchAllObjectsRequest,
+};
+use google_cloud_storage::{ChannelEvent, ChannelNotification, Client};
+use httptest::{matchers::*, responders::*, Expectation, Server};
+use prost_types::Timestamp;
+use reqwest::header::HeaderMap;
+use serde_json::json;
+use url::Url;
+
+#[tokio::test]
+async fn watch_all_objects() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/storage/v1/b/bucket/o/watch"),
+            request::query(url_decoded(contains(("prefix", "logs/")))),
+            request::body(json_decoded(eq(json!({
+                "id": "channel",
+                "type": "web_hook",
+                "address": "https://example.com/notify",
+                "expiration": "1600000000123",
+            })))),
+        ])
+        .respond_with(status_code(200).body(
+            r#"{
+                "kind": "api#channel",
+                "id": "channel",
+                "resourceId": "resource",
+                "resourceUri": "https://www.googleapis.com/storage/v1/b/bucket/o",
+                "expiration": "1600000000123"
+            }"#,
+        )),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/storage/v1/channels/stop"),
+            request::body(json_decoded(eq(json!({
+                "id": "channel",
+                "resourceId": "resource",
+                "resourceUri": "https://www.googleapis.com/storage/v1/b/bucket/o",
+                "expiration": "1600000000123",
+            })))),
+        ])
+        .respond_with(status_code(204)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let channel = client
+        .watch_all_objects(WatchAllObjectsRequest {
+            bucket: "bucket".to_string(),
+            prefix: "logs/".to_string(),
+            channel: Some(Channel {
+                id: "channel".to_string(),
+                r#type: "web_hook".to_string(),
+                address: "https://example.com/notify".to_string(),
+                expiration: Some(Timestamp {
+                    seconds: 1600000000,
+                    nanos: 123_000_000,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(channel.resource_id, "resource");
+    assert_eq!(
+        channel.expiration,
+        Some(Timestamp {
+            seconds: 1600000000,
+            nanos: 123_000_000,
+        })
+    );
+
+    client
+        .stop_channel(StopChannelRequest::from(channel))
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_channels() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/storage/v1/b/bucket/channels"))
+            .respond_with(status_code(200).body(
+                r#"{
+                    "items": [{
+                        "channel_id": "channel",
+                        "resource_id": "resource",
+                        "push_url": "https://example.com/notify",
+                        "subscriber_email": "user@example.com",
+                        "creation_time_ms": "1600000000000"
+                    }]
+                }"#,
+            )),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let channels = client
+        .list_channels(ListChannelsRequest {
+            bucket: "bucket".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let channel = &channels.items[0];
+    assert_eq!(channel.channel_id, "channel");
+    assert_eq!(channel.push_url, "https://example.com/notify");
+    assert_eq!(
+        channel.creation_time,
+        Some(Timestamp {
+            seconds: 1600000000,
+            nanos: 0,
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn channel_notification() -> Result<(), Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Goog-Channel-ID", "channel".parse()?);
+    headers.insert("X-Goog-Channel-Token", "token".parse()?);
+    headers.insert("X-Goog-Message-Number", "2".parse()?);
+    headers.insert("X-Goog-Resource-ID", "resource".parse()?);
+    headers.insert(
+        "X-Goog-Resource-URI",
+        "https://www.googleapis.com/storage/v1/b/bucket/o/object".parse()?,
+    );
+    headers.insert("X-Goog-Resource-State", "not_exists".parse()?);
+
+    let notification = ChannelNotification::from_request(
+        &headers,
+        br#"{"kind": "storage#object", "bucket": "bucket", "name": "object"}"#,
+    )?;
+
+    assert_eq!(notification.channel_id, "channel");
+    assert_eq!(notification.channel_token.as_deref(), Some("token"));
+    assert_eq!(notification.message_number, 2);
+
+    match notification.event {
+        ChannelEvent::NotExists(object) => assert_eq!(object.name, "object"),
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    headers.insert("X-Goog-Resource-State", "sync".parse()?);
+
+    let notification = ChannelNotification::from_request(&headers, b"")?;
+
+    assert_eq!(notification.event, ChannelEvent::Sync);
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+mod util;
+
+use async_trait::async_trait;
+use google_cloud_storage::storage::v1::GetProjectServiceAccountRequest;
+use google_cloud_storage::{
+    AccessToken, AuthorizedUserCredentials, CachedCredentials, Client, Headers,
+    ImpersonatedCredentials, MetadataServerCredentials, ServiceAccountCredentials, TokenSource,
+};
+use httptest::{all_of, matchers::*, responders::*, Expectation, Server};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use url::Url;
+
+#[tokio::test]
+async fn service_account_credentials() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/token"),
+            request::body(url_decoded(contains((
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:jwt-bearer"
+            )))),
+            request::body(url_decoded(contains(key("assertion")))),
+        ])
+        .times(1)
+        .respond_with(json_encoded(json!({
+            "access_token": "ya29.token",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/projects/my-project/serviceAccount"),
+            request::headers(contains(("authorization", "Bearer ya29.token"))),
+        ])
+        .times(2)
+        .respond_with(status_code(200).body(r#"{"email_address": "service@example.com"}"#)),
+    );
+
+    let credentials = ServiceAccountCredentials::from_json(serde_json::to_vec(&json!({
+        "type": "service_account",
+        "project_id": "my-project",
+        "private_key_id": "0123456789abcdef",
+        "private_key": util::PRIVATE_KEY,
+        "client_email": "test@my-project.iam.gserviceaccount.com",
+        "token_uri": "https://oauth2.googleapis.com/token"
+    }))?)?
+    .token_uri(Url::parse(server.url_str("/token").as_str())?);
+
+    assert_eq!(
+        credentials.client_email(),
+        "test@my-project.iam.gserviceaccount.com"
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let headers: Box<dyn Headers> = Box::new(credentials);
+    let client = Client::builder()
+        .base_url(base_url)
+        .headers(headers)
+        .build()?;
+
+    for _ in 0..2 {
+        client
+            .get_service_account(GetProjectServiceAccountRequest {
+                project_id: "my-project".to_string(),
+                ..Default::default()
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_authorized_user_credentials() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/token"),
+            request::body(url_decoded(contains(("refresh_token", "refresh-token")))),
+        ])
+        .times(1)
+        .respond_with(json_encoded(json!({
+            "access_token": "ya29.user",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/projects/my-project/serviceAccount"),
+            request::headers(contains(("authorization", "Bearer ya29.user"))),
+        ])
+        .times(2)
+        .respond_with(status_code(200).body(r#"{"email_address": "service@example.com"}"#)),
+    );
+
+    let source = AuthorizedUserCredentials::from_json(serde_json::to_vec(&json!({
+        "type": "authorized_user",
+        "client_id": "client-id",
+        "client_secret": "client-secret",
+        "refresh_token": "refresh-token"
+    }))?)?
+    .token_uri(Url::parse(server.url_str("/token").as_str())?);
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let headers: Box<dyn Headers> = Box::new(CachedCredentials::new(source));
+    let client = Client::builder()
+        .base_url(base_url)
+        .headers(headers)
+        .build()?;
+
+    for _ in 0..2 {
+        client
+            .get_service_account(GetProjectServiceAccountRequest {
+                project_id: "my-project".to_string(),
+                ..Default::default()
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn authorized_user_credentials_share_one_token() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    // without expires_in the token is still cached, and it is used for every scope
+    server.expect(
+        Expectation::matching(request::method_path("POST", "/token"))
+            .times(1)
+            .respond_with(json_encoded(json!({
+                "access_token": "ya29.user",
+                "token_type": "Bearer"
+            }))),
+    );
+
+    let credentials = AuthorizedUserCredentials::from_json(serde_json::to_vec(&json!({
+        "type": "authorized_user",
+        "client_id": "client-id",
+        "client_secret": "client-secret",
+        "refresh_token": "refresh-token"
+    }))?)?
+    .token_uri(Url::parse(server.url_str("/token").as_str())?);
+
+    for scope in &["a", "b", "a"] {
+        let headers = credentials.headers(scope).await?;
+        assert_eq!(headers["authorization"], "Bearer ya29.user");
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn metadata_server_credentials() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path(
+                "GET",
+                "/computeMetadata/v1/instance/service-accounts/default/token"
+            ),
+            request::headers(contains(("metadata-flavor", "Google"))),
+            request::query(url_decoded(contains((
+                "scopes",
+                "https://www.googleapis.com/auth/devstorage.read_write"
+            )))),
+        ])
+        .times(1)
+        .respond_with(json_encoded(json!({
+            "access_token": "ya29.metadata",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))),
+    );
+
+    let credentials = MetadataServerCredentials::new().host(server.addr().to_string());
+
+    for _ in 0..2 {
+        let headers = credentials
+            .headers("https://www.googleapis.com/auth/devstorage.read_write")
+            .await?;
+
+        assert_eq!(headers["authorization"], "Bearer ya29.metadata");
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn metadata_server_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+        ))
+        .times(1)
+        .respond_with(|| {
+            std::thread::sleep(Duration::from_secs(2));
+            status_code(500)
+        }),
+    );
+
+    let credentials = MetadataServerCredentials::new()
+        .host(server.addr().to_string())
+        .timeout(Duration::from_millis(200));
+
+    let start = std::time::Instant::now();
+
+    assert!(credentials.headers("a").await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn impersonated_credentials() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path(
+                "POST",
+                "/v1/projects/-/serviceAccounts/writer@my-project.iam.gserviceaccount.com:generateAccessToken"
+            ),
+            request::headers(contains(("authorization", "Bearer ya29.source"))),
+            request::body(json_decoded(eq(json!({
+                "delegates": ["projects/-/serviceAccounts/ci@my-project.iam.gserviceaccount.com"],
+                "scope": ["https://www.googleapis.com/auth/devstorage.read_write"],
+                "lifetime": "600s"
+            })))),
+        ])
+        .times(1)
+        .respond_with(json_encoded(json!({
+            "accessToken": "ya29.impersonated",
+            "expireTime": "2999-01-01T00:00:00Z"
+        }))),
+    );
+
+    server.expect(
+        Expectation::matching(request::method_path("POST", "/token")).respond_with(json_encoded(
+            json!({
+                "access_token": "ya29.source",
+                "expires_in": 3599,
+                "token_type": "Bearer"
+            }),
+        )),
+    );
+
+    let source = ServiceAccountCredentials::from_json(serde_json::to_vec(&json!({
+        "type": "service_account",
+        "private_key": util::PRIVATE_KEY,
+        "client_email": "ci@my-project.iam.gserviceaccount.com"
+    }))?)?
+    .token_uri(Url::parse(server.url_str("/token").as_str())?);
+
+    let credentials =
+        ImpersonatedCredentials::new(source, "writer@my-project.iam.gserviceaccount.com")?
+            .delegates(vec!["ci@my-project.iam.gserviceaccount.com"])
+            .lifetime(Duration::from_secs(600))
+            .endpoint(Url::parse(server.url_str("/v1/").as_str())?);
+
+    for _ in 0..2 {
+        let headers = credentials
+            .headers("https://www.googleapis.com/auth/devstorage.read_write")
+            .await?;
+
+        assert_eq!(headers["authorization"], "Bearer ya29.impersonated");
+    }
+
+    Ok(())
+}
+
+/// Counts the tokens it hands out, failing for the `fail` scope
+#[derive(Default)]
+struct CountingSource {
+    count: AtomicUsize,
+}
+
+#[async_trait]
+impl TokenSource for CountingSource {
+    async fn token(&self, scope: &str) -> google_cloud_storage::Result<AccessToken> {
+        let count = self.count.fetch_add(1, Ordering::SeqCst);
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+
+        if scope == "fail" {
+            return Err(google_cloud_storage::Error::Other {
+                source: "token endpoint unavailable".into(),
+            });
+        }
+
+        Ok(AccessToken::new(
+            format!("{}-{}", scope, count),
+            Duration::from_secs(3600),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn cached_credentials_single_flight() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let credentials = CachedCredentials::new(CountingSource::default());
+
+    let headers = futures::future::try_join_all((0..8).map(|_| credentials.headers("a"))).await?;
+    assert!(headers
+        .iter()
+        .all(|headers| headers["authorization"] == "Bearer a-0"));
+
+    let headers = credentials.headers("b").await?;
+    assert_eq!(headers["authorization"], "Bearer b-1");
+
+    let headers = credentials.headers("a").await?;
+    assert_eq!(headers["authorization"], "Bearer a-0");
+
+    match credentials.headers("fail").await {
+        Err(google_cloud_storage::Error::TokenRefresh { scope, .. }) => assert_eq!(scope, "fail"),
+        other => panic!("expected a token refresh error, got {:?}", other),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+mod util;
+
+use async_trait::async_trait;
+use google_cloud_storage::storage::v1::GetProjectServiceAccountRequest;
+use google_cloud_storage::{Client, Headers};
+use httptest::{all_of, matchers::*, responders::*, Expectation, Server};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use url::Url;
+
+struct ScopeToken;
+
+#[async_trait]
+impl Headers for ScopeToken {
+    async fn headers(&self, scope: &str) -> google_cloud_storage::Result<HeaderMap<HeaderValue>> {
+        let mut map = HeaderMap::with_capacity(1);
+        map.insert(AUTHORIZATION, format!("Bearer {}", scope).parse()?);
+        Ok(map)
+    }
+}
+
+#[tokio::test]
+async fn async_headers() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/projects/my-project/serviceAccount"),
+            request::headers(contains((
+                "authorization",
+                "Bearer https://www.googleapis.com/auth/devstorage.read_only"
+            ))),
+        ])
+        .respond_with(status_code(200).body(r#"{"email_address": "service@example.com"}"#)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let headers: Box<dyn Headers> = Box::new(ScopeToken);
+    let client = Client::builder()
+        .base_url(base_url)
+        .headers(headers)
+        .build()?;
+
+    let service_account = client
+        .get_service_account(GetProjectServiceAccountRequest {
+            project_id: "my-project".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(service_account.email_address, "service@example.com");
+
+    Ok(())
+}
+
+#[cfg(feature = "yup-oauth2")]
+#[tokio::test]
+async fn authenticator_with_custom_connector() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path("POST", "/token")).respond_with(
+            status_code(200)
+                .body(r#"{"access_token": "token", "token_type": "Bearer", "expires_in": 3600}"#),
+        ),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/projects/my-project/serviceAccount"),
+            request::headers(contains(("authorization", "Bearer token"))),
+        ])
+        .respond_with(status_code(200).body(r#"{"email_address": "service@example.com"}"#)),
+    );
+
+    let key = yup_oauth2::ServiceAccountKey {
+        key_type: Some("service_account".to_string()),
+        project_id: None,
+        private_key_id: None,
+        private_key: util::PRIVATE_KEY.to_string(),
+        client_email: "test@example.com".to_string(),
+        client_id: None,
+        auth_uri: None,
+        token_uri: server.url_str("/token"),
+        auth_provider_x509_cert_url: None,
+        client_x509_cert_url: None,
+    };
+
+    // a plain http connector instead of the https one the builders default to
+    let authenticator = yup_oauth2::ServiceAccountAuthenticator::builder(key)
+        .hyper_client(hyper::Client::builder().build_http())
+        .build()
+        .await?;
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder()
+        .base_url(base_url)
+        .authenticator(authenticator)
+        .build()?;
+
+    let service_account = client
+        .get_service_account(GetProjectServiceAccountRequest {
+            project_id: "my-project".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(service_account.email_address, "service@example.com");
+
+    Ok(())
+}
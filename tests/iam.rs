@@ -0,0 +1,89 @@
+mod util;
+
+use google_cloud_storage::iam::v1::{self as iam, Binding, Policy};
+use google_cloud_storage::storage::v1::{SetIamPolicyRequest, TestIamPermissionsRequest};
+use google_cloud_storage::Client;
+use httptest::{matchers::*, responders::*, Expectation, Server};
+use serde_json::json;
+use url::Url;
+
+#[tokio::test]
+async fn set_bucket_iam_policy() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/storage/v1/b/bucket/iam"),
+            request::body(json_decoded(eq(json!({
+                "bindings": [{"role": "roles/storage.objectViewer", "members": ["allUsers"]}],
+            })))),
+        ])
+        .respond_with(status_code(200).body(
+            r#"{"version": 1, "bindings": [{"role": "roles/storage.objectViewer", "members": ["allUsers"]}]}"#,
+        )),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let policy = client
+        .set_bucket_iam_policy(SetIamPolicyRequest {
+            iam_request: Some(iam::SetIamPolicyRequest {
+                resource: "bucket".to_string(),
+                policy: Some(Policy {
+                    bindings: vec![Binding {
+                        role: "roles/storage.objectViewer".to_string(),
+                        members: vec!["allUsers".to_string()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(policy.version, 1);
+    assert_eq!(policy.bindings.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bucket_iam_permissions() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/b/bucket/iam/testPermissions"),
+            request::query(url_decoded(contains((
+                "permissions",
+                "storage.buckets.get"
+            )))),
+        ])
+        .respond_with(status_code(200).body(r#"{"permissions": ["storage.buckets.get"]}"#)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let response = client
+        .test_bucket_iam_permissions(TestIamPermissionsRequest {
+            iam_request: Some(iam::TestIamPermissionsRequest {
+                resource: "bucket".to_string(),
+                permissions: vec!["storage.buckets.get".to_string()],
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(response.permissions, vec!["storage.buckets.get"]);
+
+    Ok(())
+}
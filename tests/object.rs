@@ -0,0 +1,1060 @@
+mod util;
+
+use bytes::Bytes;
+use futures::{stream, TryStreamExt};
+use google_cloud_storage::iam::v1::{self as iam, Binding, Policy};
+use google_cloud_storage::iam::ObjectResource;
+use google_cloud_storage::storage::v1::insert_object_request::{Data, FirstMessage};
+use google_cloud_storage::storage::v1::{
+    ChecksummedData, GetIamPolicyRequest, GetObjectMediaRequest, InsertObjectRequest,
+    InsertObjectSpec, Object, PatchObjectRequest, QueryWriteStatusRequest, SetIamPolicyRequest,
+    StartResumableWriteRequest, TestIamPermissionsRequest,
+};
+use google_cloud_storage::{Client, ResumableUpload, RetryPolicy};
+use httptest::{cycle, matchers::*, responders::*, Expectation, Server};
+use prost_types::FieldMask;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use url::Url;
+
+#[tokio::test]
+async fn insert_object_resumable() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/upload/storage/v1/b/bucket/o"),
+            request::query(url_decoded(contains(("uploadType", "resumable")))),
+            request::headers(contains(("x-upload-content-type", "text/plain"))),
+        ])
+        .respond_with(
+            status_code(200)
+                .insert_header("Location", server.url_str("/upload/session?upload_id=1")),
+        ),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-4/5"))),
+            request::headers(contains((
+                "x-goog-hash",
+                format!(
+                    "crc32c={},md5={}",
+                    base64::encode(crc32c::crc32c(b"hello").to_be_bytes()),
+                    base64::encode(md5::compute(b"hello").0)
+                )
+            ))),
+            request::body("hello"),
+        ])
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "5"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .insert_object_resumable(
+            InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    content_type: "text/plain".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+            stream::iter(vec![Bytes::from("hel"), Bytes::from("lo")]),
+        )
+        .await?;
+
+    assert_eq!(object.name, "object");
+    assert_eq!(object.size, 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_write_status() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-262143")),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let status = client
+        .query_write_status(QueryWriteStatusRequest {
+            upload_id: server.url_str("/upload/session?upload_id=1"),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(status.committed_size, 262144);
+    assert!(!status.complete);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn continue_resumable_upload() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "POST",
+            "/upload/storage/v1/b/bucket/o",
+        ))
+        .respond_with(
+            status_code(200)
+                .insert_header("Location", server.url_str("/upload/session?upload_id=1")),
+        ),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-262143/*"))),
+        ])
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-262143")),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-262143")),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 262144-262148/262149"))),
+            // the MD5 in progress is not persisted, so only the CRC32C is sent
+            request::headers(contains((
+                "x-goog-hash",
+                format!(
+                    "crc32c={}",
+                    base64::encode(
+                        crc32c::crc32c_append(crc32c::crc32c(&[0u8; 256 * 1024]), b"hello")
+                            .to_be_bytes()
+                    )
+                )
+            ))),
+            request::body("hello"),
+        ])
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "262149"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let chunk = Bytes::from(vec![0u8; 256 * 1024]);
+
+    let mut upload = client
+        .start_resumable_upload(StartResumableWriteRequest {
+            insert_object_spec: Some(InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    client
+        .write_resumable_upload(&mut upload, chunk.clone())
+        .await?;
+
+    assert_eq!(upload.committed_size(), 262144);
+    assert_eq!(upload.crc32c(), crc32c::crc32c(&chunk));
+
+    let persisted = serde_json::to_string(&upload)?;
+
+    let mut upload = serde_json::from_str::<ResumableUpload>(&persisted)?;
+
+    let object = client
+        .continue_resumable_upload(&mut upload, stream::iter(vec![Bytes::from("hello")]))
+        .await?;
+
+    assert_eq!(object.size, 262149);
+    assert_eq!(upload.committed_size(), 262149);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn continue_finalized_upload_is_verified() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "POST",
+            "/upload/storage/v1/b/bucket/o",
+        ))
+        .respond_with(
+            status_code(200)
+                .insert_header("Location", server.url_str("/upload/session?upload_id=1")),
+        ),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-262143/*"))),
+        ])
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-262143")),
+    );
+
+    let chunk = Bytes::from(vec![0u8; 256 * 1024]);
+
+    // the final chunk was committed but the response was lost
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .times(2)
+        .respond_with(json_encoded(json!({
+            "bucket": "bucket",
+            "name": "object",
+            "size": "262149",
+            "crc32c": base64::encode(crc32c::crc32c_append(crc32c::crc32c(&chunk), b"hello").to_be_bytes())
+        }))),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let mut upload = client
+        .start_resumable_upload(StartResumableWriteRequest {
+            insert_object_spec: Some(InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    client.write_resumable_upload(&mut upload, chunk).await?;
+
+    let object = client
+        .continue_resumable_upload(
+            &mut upload.clone(),
+            stream::iter(vec![Bytes::from("hello")]),
+        )
+        .await?;
+
+    assert_eq!(object.size, 262149);
+
+    let result = client
+        .continue_resumable_upload(&mut upload, stream::iter(vec![Bytes::from("jello")]))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(google_cloud_storage::Error::ChecksumMismatch {
+            algorithm: "crc32c",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy::default().initial_backoff(Duration::from_millis(1))
+}
+
+/// Start a resumable upload of "hello world" against a server that opens a session
+async fn resume_hello_world(
+    server: &Server,
+    retry_policy: RetryPolicy,
+) -> google_cloud_storage::Result<Object> {
+    server.expect(
+        Expectation::matching(request::method_path(
+            "POST",
+            "/upload/storage/v1/b/bucket/o",
+        ))
+        .respond_with(
+            status_code(200)
+                .insert_header("Location", server.url_str("/upload/session?upload_id=1")),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str()).unwrap();
+
+    let client = Client::builder()
+        .base_url(base_url)
+        .retry_policy(retry_policy)
+        .build()?;
+
+    client
+        .insert_object_resumable(
+            InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+            stream::iter(vec![Bytes::from("hello world")]),
+        )
+        .await
+}
+
+#[tokio::test]
+async fn resume_after_transient_error() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(1)
+        .respond_with(status_code(503)),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .times(1)
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-4")),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 5-10/11"))),
+            request::body(" world"),
+        ])
+        .times(1)
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "11"}"#),
+        ),
+    );
+
+    let object = resume_hello_world(&server, fast_retries()).await?;
+
+    assert_eq!(object.size, 11);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_backs_off_and_retries_status_query() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(1)
+        .respond_with(status_code(503)),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .times(2)
+        .respond_with(cycle![
+            status_code(429),
+            status_code(308).insert_header("Range", "bytes=0-4"),
+        ]),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 5-10/11"))),
+            request::body(" world"),
+        ])
+        .times(1)
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "11"}"#),
+        ),
+    );
+
+    let start = Instant::now();
+
+    let object = resume_hello_world(
+        &server,
+        RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(100))
+            .jitter(0.0),
+    )
+    .await?;
+
+    assert_eq!(object.size, 11);
+    // 100ms after the failed write and 200ms after the failed status query
+    assert!(start.elapsed() >= Duration::from_millis(300));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_gives_up_after_max_attempts() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(1)
+        .respond_with(status_code(503)),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes */*"))),
+        ])
+        .times(1)
+        .respond_with(status_code(503)),
+    );
+
+    assert!(resume_hello_world(&server, fast_retries().max_attempts(2))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_after_partial_commit() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(1)
+        .respond_with(status_code(308).insert_header("Range", "bytes=0-6")),
+    );
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 7-10/11"))),
+            request::body("orld"),
+        ])
+        .times(1)
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "11"}"#),
+        ),
+    );
+
+    let object = resume_hello_world(&server, fast_retries()).await?;
+
+    assert_eq!(object.size, 11);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_gives_up_without_progress() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    // the first attempt and three more that each commit nothing
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(4)
+        .respond_with(status_code(308)),
+    );
+
+    assert!(resume_hello_world(&server, fast_retries()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_not_attempted_after_client_error() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    // no status query follows a failed precondition
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/upload/session"),
+            request::headers(contains(("content-range", "bytes 0-10/11"))),
+        ])
+        .times(1)
+        .respond_with(status_code(412)),
+    );
+
+    assert!(resume_hello_world(&server, fast_retries()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn patch_object() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PATCH", "/storage/v1/b/bucket/o/object"),
+            request::query(url_decoded(contains(("ifMetagenerationMatch", "3")))),
+            request::body(json_decoded(eq(json!({
+                "contentType": "text/plain",
+                "cacheControl": null,
+                "metadata": {"color": "red", "shape": null},
+            })))),
+        ])
+        .respond_with(
+            status_code(200)
+                .body(r#"{"bucket": "bucket", "name": "object", "contentType": "text/plain"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .patch_object(PatchObjectRequest {
+            bucket: "bucket".to_string(),
+            object: "object".to_string(),
+            if_metageneration_match: Some(3),
+            metadata: Some(Object {
+                content_type: "text/plain".to_string(),
+                content_encoding: "gzip".to_string(),
+                metadata: vec![("color".to_string(), "red".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            }),
+            update_mask: Some(FieldMask {
+                paths: vec![
+                    "content_type".to_string(),
+                    "cache_control".to_string(),
+                    "metadata.color".to_string(),
+                    "metadata.shape".to_string(),
+                ],
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(object.content_type, "text/plain");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insert_object_multipart() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/upload/storage/v1/b/bucket/o"),
+            request::query(url_decoded(contains(("uploadType", "multipart")))),
+            request::query(url_decoded(contains(("ifGenerationMatch", "0")))),
+            request::headers(contains((
+                "content-type",
+                matches("^multipart/related; boundary=[0-9a-f]{32}$")
+            ))),
+            request::body(matches(
+                "^--[0-9a-f]{32}\r\n\
+                Content-Type: application/json; charset=UTF-8\r\n\r\n\
+                \\{[^\\r]*\"name\":\"object\"[^\\r]*\\}\r\n\
+                --[0-9a-f]{32}\r\n\
+                Content-Type: text/plain\r\n\r\n\
+                hello\r\n\
+                --[0-9a-f]{32}--\r\n$"
+            )),
+            request::body(matches("\"cacheControl\":\"no-cache\"")),
+        ])
+        .respond_with(
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "5"}"#),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .insert_object_stream::<stream::Iter<_>>(
+            InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    content_type: "text/plain".to_string(),
+                    cache_control: "no-cache".to_string(),
+                    ..Default::default()
+                }),
+                if_generation_match: Some(0),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+            stream::iter(vec![Bytes::from("hel"), Bytes::from("lo")]),
+        )
+        .await?;
+
+    assert_eq!(object.name, "object");
+    assert_eq!(object.size, 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_object_iam_policy() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PUT", "/storage/v1/b/bucket/o/foo%2Fbar/iam"),
+            request::body(json_decoded(eq(json!({
+                "bindings": [{"role": "roles/storage.objectViewer", "members": ["allUsers"]}],
+            })))),
+        ])
+        .respond_with(status_code(200).body(
+            r#"{"version": 1, "bindings": [{"role": "roles/storage.objectViewer", "members": ["allUsers"]}]}"#,
+        )),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let policy = client
+        .set_object_iam_policy(
+            ObjectResource {
+                bucket: "bucket".to_string(),
+                object: "foo/bar".to_string(),
+                ..Default::default()
+            },
+            SetIamPolicyRequest {
+                iam_request: Some(iam::SetIamPolicyRequest {
+                    policy: Some(Policy {
+                        bindings: vec![Binding {
+                            role: "roles/storage.objectViewer".to_string(),
+                            members: vec!["allUsers".to_string()],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    assert_eq!(policy.version, 1);
+    assert_eq!(policy.bindings.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_object_iam_permissions() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/b/bucket/o/object/iam/testPermissions"),
+            request::query(url_decoded(contains((
+                "permissions",
+                "storage.objects.get"
+            )))),
+            request::query(url_decoded(contains(("generation", "3")))),
+        ])
+        .respond_with(status_code(200).body(r#"{"permissions": ["storage.objects.get"]}"#)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let response = client
+        .test_object_iam_permissions(
+            ObjectResource {
+                bucket: "bucket".to_string(),
+                object: "object".to_string(),
+                generation: 3,
+            },
+            TestIamPermissionsRequest {
+                iam_request: Some(iam::TestIamPermissionsRequest {
+                    permissions: vec!["storage.objects.get".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    assert_eq!(response.permissions, vec!["storage.objects.get"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_object_iam_policy() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/b/bucket/o/a%20b%2Fc/iam",
+        ))
+        .respond_with(status_code(200).body(r#"{"version": 1}"#)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let policy = client
+        .get_object_iam_policy(
+            Object {
+                bucket: "bucket".to_string(),
+                name: "a b/c".to_string(),
+                ..Default::default()
+            },
+            GetIamPolicyRequest::default(),
+        )
+        .await?;
+
+    assert_eq!(policy.version, 1);
+
+    Ok(())
+}
+
+/// The `x-goog-hash` header for `data`
+fn goog_hash(data: &[u8]) -> String {
+    format!(
+        "crc32c={},md5={}",
+        base64::encode(crc32c::crc32c(data).to_be_bytes()),
+        base64::encode(md5::compute(data).0)
+    )
+}
+
+#[tokio::test]
+async fn get_object_media_checksums() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/storage/v1/b/bucket/o/valid"))
+            .times(2)
+            .respond_with(
+                status_code(200)
+                    .insert_header("x-goog-hash", goog_hash(b"hello"))
+                    .body("hello"),
+            ),
+    );
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/b/bucket/o/corrupt",
+        ))
+        .times(2)
+        .respond_with(
+            status_code(200)
+                .insert_header("x-goog-hash", goog_hash(b"hello"))
+                .body("jello"),
+        ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let valid = GetObjectMediaRequest {
+        bucket: "bucket".to_string(),
+        object: "valid".to_string(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        client.get_object_media_bytes(valid.clone()).await?,
+        b"hello"
+    );
+
+    let chunks: Vec<Bytes> = client
+        .get_object_media_stream(valid)
+        .await?
+        .try_collect()
+        .await?;
+    assert_eq!(chunks.concat(), b"hello");
+
+    let corrupt = GetObjectMediaRequest {
+        bucket: "bucket".to_string(),
+        object: "corrupt".to_string(),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        client.get_object_media_bytes(corrupt.clone()).await,
+        Err(google_cloud_storage::Error::ChecksumMismatch {
+            algorithm: "crc32c",
+            ..
+        })
+    ));
+
+    let result: Result<Vec<Bytes>, _> = client
+        .get_object_media_stream(corrupt)
+        .await?
+        .try_collect()
+        .await;
+    assert!(matches!(
+        result,
+        Err(google_cloud_storage::Error::ChecksumMismatch { .. })
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_object_media_skips_checksums() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    // the hashes are of the whole object, not the range that is returned
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/storage/v1/b/bucket/o/range"),
+            request::headers(contains(("range", "bytes=1-3"))),
+        ])
+        .respond_with(
+            status_code(206)
+                .insert_header("x-goog-hash", goog_hash(b"hello"))
+                .body("ell"),
+        ),
+    );
+
+    // the hashes are of the stored gzip data, not the decompressed data that is returned
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/storage/v1/b/bucket/o/gzip"))
+            .respond_with(
+                status_code(200)
+                    .insert_header("x-goog-hash", goog_hash(b"compressed"))
+                    .insert_header("x-goog-stored-content-encoding", "gzip")
+                    .body("hello"),
+            ),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let range = client
+        .get_object_media_bytes(GetObjectMediaRequest {
+            bucket: "bucket".to_string(),
+            object: "range".to_string(),
+            read_offset: 1,
+            read_limit: 3,
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(range, b"ell");
+
+    let chunks: Vec<Bytes> = client
+        .get_object_media_stream(GetObjectMediaRequest {
+            bucket: "bucket".to_string(),
+            object: "gzip".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .try_collect()
+        .await?;
+    assert_eq!(chunks.concat(), b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insert_object_checksums() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    let crc32c = base64::encode(crc32c::crc32c(b"hello").to_be_bytes());
+    let md5 = base64::encode(md5::compute(b"hello").0);
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/upload/storage/v1/b/bucket/o"),
+            request::body(matches(format!("\"crc32c\":\"{}\"", crc32c))),
+            request::body(matches(format!("\"md5Hash\":\"{}\"", md5))),
+        ])
+        .respond_with(json_encoded(json!({
+            "bucket": "bucket",
+            "name": "object",
+            "size": "5",
+            "crc32c": crc32c,
+            "md5Hash": md5
+        }))),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let object = client
+        .insert_object(InsertObjectRequest {
+            first_message: Some(FirstMessage::InsertObjectSpec(InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            data: Some(Data::ChecksummedData(ChecksummedData {
+                content: b"hello".to_vec(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(object.crc32c, Some(crc32c::crc32c(b"hello")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insert_object_checksum_mismatch() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "POST",
+            "/upload/storage/v1/b/bucket/o",
+        ))
+        .times(2)
+        .respond_with(json_encoded(json!({
+            "bucket": "bucket",
+            "name": "object",
+            "generation": "7",
+            "crc32c": base64::encode(crc32c::crc32c(b"jello").to_be_bytes())
+        }))),
+    );
+
+    // only the generation that was just written is deleted
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("DELETE", "/storage/v1/b/bucket/o/object"),
+            request::query(url_decoded(contains(("generation", "7")))),
+        ])
+        .times(1)
+        .respond_with(status_code(204)),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let spec = InsertObjectSpec {
+        resource: Some(Object {
+            bucket: "bucket".to_string(),
+            name: "object".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    for delete in &[false, true] {
+        let client = Client::builder()
+            .base_url(base_url.clone())
+            .delete_on_checksum_mismatch(*delete)
+            .build()?;
+
+        let result = client
+            .insert_object_stream::<stream::Iter<_>>(
+                spec.clone(),
+                None,
+                None,
+                None,
+                stream::iter(vec![Bytes::from("hel"), Bytes::from("lo")]),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(google_cloud_storage::Error::ChecksumMismatch {
+                algorithm: "crc32c",
+                ..
+            })
+        ));
+    }
+
+    Ok(())
+}
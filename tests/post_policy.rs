@@ -0,0 +1,122 @@
+mod util;
+
+use chrono::{TimeZone, Utc};
+use google_cloud_storage::{PostPolicyV4, UrlSigner, UrlStyle};
+use ring::signature::{KeyPair, RsaKeyPair, UnparsedPublicKey, RSA_PKCS1_2048_8192_SHA256};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+fn policy() -> PostPolicyV4 {
+    PostPolicyV4::new("test-bucket", "uploads/${filename}")
+        .timestamp(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())
+        .expires_in(Duration::from_secs(10))
+        .key_starts_with("uploads/")
+        .content_length_range(0, 1024)
+        .content_type("image/png")
+        .acl("public-read")
+        .success_action_redirect("https://example.com/done")
+        .metadata("owner", "café")
+}
+
+#[tokio::test]
+async fn post_policy_rsa() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let signer = UrlSigner::from_json(serde_json::to_vec(&json!({
+        "type": "service_account",
+        "private_key": util::PRIVATE_KEY,
+        "client_email": "test@my-project.iam.gserviceaccount.com"
+    }))?)?;
+
+    let post_policy = policy().sign(&signer).await?;
+
+    assert_eq!(
+        post_policy.url.as_str(),
+        "https://storage.googleapis.com/test-bucket/"
+    );
+
+    let fields = &post_policy.fields;
+    assert_eq!(fields["key"], "uploads/${filename}");
+    assert_eq!(fields["Content-Type"], "image/png");
+    assert_eq!(fields["acl"], "public-read");
+    assert_eq!(
+        fields["success_action_redirect"],
+        "https://example.com/done"
+    );
+    assert_eq!(fields["x-goog-meta-owner"], "café");
+    assert_eq!(fields["x-goog-algorithm"], "GOOG4-RSA-SHA256");
+    assert_eq!(
+        fields["x-goog-credential"],
+        "test@my-project.iam.gserviceaccount.com/20200101/auto/storage/goog4_request"
+    );
+    assert_eq!(fields["x-goog-date"], "20200101T000000Z");
+
+    let document = String::from_utf8(base64::decode(&fields["policy"])?)?;
+    // non-ASCII characters are escaped in the policy document
+    assert!(document.contains(r#"{"x-goog-meta-owner":"caf\u00e9"}"#));
+
+    let document: Value = serde_json::from_str(&document)?;
+    assert_eq!(
+        document,
+        json!({
+            "conditions": [
+                ["content-length-range", 0, 1024],
+                {"Content-Type": "image/png"},
+                {"acl": "public-read"},
+                {"success_action_redirect": "https://example.com/done"},
+                {"x-goog-meta-owner": "café"},
+                {"bucket": "test-bucket"},
+                ["starts-with", "$key", "uploads/"],
+                {"x-goog-date": "20200101T000000Z"},
+                {"x-goog-credential": "test@my-project.iam.gserviceaccount.com/20200101/auto/storage/goog4_request"},
+                {"x-goog-algorithm": "GOOG4-RSA-SHA256"}
+            ],
+            "expiration": "2020-01-01T00:00:10Z"
+        })
+    );
+
+    let der = base64::decode(
+        util::PRIVATE_KEY
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect::<String>(),
+    )?;
+    let key_pair = RsaKeyPair::from_pkcs8(&der).map_err(|e| e.to_string())?;
+
+    let signature = &fields["x-goog-signature"];
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, key_pair.public_key().as_ref())
+        .verify(fields["policy"].as_bytes(), &signature)
+        .map_err(|_| "invalid signature")?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn post_policy_hmac() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let signer = UrlSigner::hmac("GOOG1EXAMPLE", "c2VjcmV0");
+
+    let post_policy = policy()
+        .style(UrlStyle::VirtualHostedStyle)
+        .sign(&signer)
+        .await?;
+
+    assert_eq!(
+        post_policy.url.as_str(),
+        "https://test-bucket.storage.googleapis.com/"
+    );
+    assert_eq!(post_policy.fields["x-goog-algorithm"], "GOOG4-HMAC-SHA256");
+    assert_eq!(
+        post_policy.fields["x-goog-credential"],
+        "GOOG1EXAMPLE/20200101/auto/storage/goog4_request"
+    );
+    assert_eq!(post_policy.fields["x-goog-signature"].len(), 64);
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+mod util;
+
+use google_cloud_storage::storage::v1::insert_object_request::{Data, FirstMessage};
+use google_cloud_storage::storage::v1::{
+    ChecksummedData, DeleteObjectRequest, GetObjectMediaRequest, GetProjectServiceAccountRequest,
+    InsertObjectRequest, InsertObjectSpec, Object, PatchObjectRequest,
+};
+use google_cloud_storage::{Client, RetryPolicy};
+use httptest::{all_of, cycle, matchers::*, responders::*, Expectation, Server};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+use url::Url;
+
+fn request() -> GetProjectServiceAccountRequest {
+    GetProjectServiceAccountRequest {
+        project_id: "my-project".to_string(),
+        ..Default::default()
+    }
+}
+
+fn client(
+    server: &Server,
+    retry_policy: RetryPolicy,
+) -> Result<Client, Box<dyn std::error::Error>> {
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    Ok(Client::builder()
+        .base_url(base_url)
+        .retry_policy(retry_policy)
+        .build()?)
+}
+
+#[tokio::test]
+async fn retry_transient_error() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/projects/my-project/serviceAccount",
+        ))
+        .times(3)
+        .respond_with(cycle![
+            status_code(503),
+            status_code(429),
+            status_code(200).body(r#"{"email_address": "service@example.com"}"#),
+        ]),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(1))
+            .max_backoff(Duration::from_millis(10)),
+    )?;
+
+    let service_account = client.get_service_account(request()).await?;
+
+    assert_eq!(service_account.email_address, "service@example.com");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_gives_up_after_max_attempts() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/projects/my-project/serviceAccount",
+        ))
+        .times(2)
+        .respond_with(status_code(500)),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default()
+            .max_attempts(2)
+            .initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    assert!(client.get_service_account(request()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deadline_cuts_off_slow_attempt() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/projects/my-project/serviceAccount",
+        ))
+        .times(1)
+        .respond_with(|| {
+            std::thread::sleep(Duration::from_secs(2));
+            status_code(200).body(r#"{"email_address": "service@example.com"}"#)
+        }),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(1))
+            .deadline(Duration::from_millis(300)),
+    )?;
+
+    let start = Instant::now();
+
+    assert!(client.get_service_account(request()).await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deadline_does_not_cut_off_slow_body() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    // httptest sends the whole response at once, so answer with the headers straight away and
+    // trickle the body in well past the deadline
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let base_url = Url::parse(&format!("http://{}/storage/v1/", listener.local_addr()?))?;
+
+    let server = std::thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello")?;
+        stream.flush()?;
+
+        std::thread::sleep(Duration::from_millis(500));
+
+        stream.write_all(b"world")?;
+        stream.flush()
+    });
+
+    let client = Client::builder()
+        .base_url(base_url)
+        .retry_policy(RetryPolicy::default().deadline(Duration::from_millis(200)))
+        .build()?;
+
+    let bytes = client
+        .get_object_media_bytes(GetObjectMediaRequest {
+            bucket: "bucket".to_string(),
+            object: "object".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(bytes, b"helloworld");
+
+    server.join().expect("server panicked")?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_retry_on_client_error() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/projects/my-project/serviceAccount",
+        ))
+        .times(1)
+        .respond_with(status_code(404)),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default().initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    assert!(client.get_service_account(request()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_retry_without_precondition() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "DELETE",
+            "/storage/v1/b/bucket/o/object",
+        ))
+        .times(1)
+        .respond_with(status_code(503)),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default().initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    let request = DeleteObjectRequest {
+        bucket: "bucket".to_string(),
+        object: "object".to_string(),
+        ..Default::default()
+    };
+
+    assert!(client.delete_object(request).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_delete_of_generation() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("DELETE", "/storage/v1/b/bucket/o/object"),
+            request::query(url_decoded(contains(("generation", "7")))),
+        ])
+        .times(2)
+        .respond_with(cycle![status_code(503), status_code(204)]),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default().initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    let request = DeleteObjectRequest {
+        bucket: "bucket".to_string(),
+        object: "object".to_string(),
+        generation: 7,
+        ..Default::default()
+    };
+
+    client.delete_object(request).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_with_precondition() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("PATCH", "/storage/v1/b/bucket/o/object"),
+            request::query(url_decoded(contains(("ifMetagenerationMatch", "5")))),
+        ])
+        .times(2)
+        .respond_with(cycle![
+            status_code(503),
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object"}"#),
+        ]),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default().initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    let request = PatchObjectRequest {
+        bucket: "bucket".to_string(),
+        object: "object".to_string(),
+        if_metageneration_match: Some(5),
+        metadata: Some(Object::default()),
+        ..Default::default()
+    };
+
+    client.patch_object(request).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn always_retry() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "PATCH",
+            "/storage/v1/b/bucket/o/object",
+        ))
+        .times(2)
+        .respond_with(cycle![
+            status_code(503),
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object"}"#),
+        ]),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(1))
+            .always_retry(true),
+    )?;
+
+    let request = PatchObjectRequest {
+        bucket: "bucket".to_string(),
+        object: "object".to_string(),
+        metadata: Some(Object::default()),
+        ..Default::default()
+    };
+
+    client.patch_object(request).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_insert_with_precondition() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    // the whole multipart body is sent again
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/upload/storage/v1/b/bucket/o"),
+            request::query(url_decoded(contains(("ifGenerationMatch", "0")))),
+            request::body(matches("\r\n\r\nhello\r\n")),
+        ])
+        .times(2)
+        .respond_with(cycle![
+            status_code(503),
+            status_code(200).body(r#"{"bucket": "bucket", "name": "object", "size": "5"}"#),
+        ]),
+    );
+
+    let client = client(
+        &server,
+        RetryPolicy::default().initial_backoff(Duration::from_millis(1)),
+    )?;
+
+    let object = client
+        .insert_object(InsertObjectRequest {
+            first_message: Some(FirstMessage::InsertObjectSpec(InsertObjectSpec {
+                resource: Some(Object {
+                    bucket: "bucket".to_string(),
+                    name: "object".to_string(),
+                    ..Default::default()
+                }),
+                if_generation_match: Some(0),
+                ..Default::default()
+            })),
+            data: Some(Data::ChecksummedData(ChecksummedData {
+                content: b"hello".to_vec(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(object.size, 5);
+
+    Ok(())
+}
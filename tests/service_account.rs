@@ -0,0 +1,44 @@
+mod util;
+
+use google_cloud_storage::storage::v1::GetProjectServiceAccountRequest;
+use google_cloud_storage::Client;
+use httptest::{matchers::*, responders::*, Expectation, Server};
+use url::Url;
+
+#[tokio::test]
+async fn get_service_account() -> Result<(), Box<dyn std::error::Error>> {
+    util::init();
+
+    let server = Server::run();
+
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/storage/v1/projects/my-project/serviceAccount",
+        ))
+        .respond_with(status_code(200).body(
+            r#"{
+                "email_address": "service-123@gs-project-accounts.iam.gserviceaccount.com",
+                "kind": "storage#serviceAccount"
+            }"#,
+        )),
+    );
+
+    let base_url = Url::parse(server.url_str("/storage/v1/").as_str())?;
+
+    let client = Client::builder().base_url(base_url).build()?;
+
+    let service_account = client
+        .get_service_account(GetProjectServiceAccountRequest {
+            project_id: "my-project".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(
+        service_account.email_address,
+        "service-123@gs-project-accounts.iam.gserviceaccount.com"
+    );
+
+    Ok(())
+}